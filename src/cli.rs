@@ -1,4 +1,8 @@
-use crate::ascii;
+use crate::{ascii, src_code::Span, uoffset, utf32};
+use core::fmt::Write;
+use std::ffi::{OsStr, OsString};
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt as _;
 
 #[rustfmt::skip]
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
@@ -43,25 +47,24 @@ impl FlagPrefix {
     }
 }
 
-#[must_use]
-#[inline(always)]
-const fn get_byte(array: &str, index: usize) -> Option<ascii> {
-    if index >= array.len() {
-        return None;
+impl FlagPrefix {
+    /// Prefix `arg` starts with
+    #[must_use]
+    #[inline]
+    pub const fn of(arg: &[u8]) -> Self {
+        return match arg {
+            [b'/', ..] => Self::Slash,
+            [b'-', b'-', ..] => Self::DashDash,
+            [b'-', ..] => Self::Dash,
+            _ => Self::Empty,
+        };
     }
-    return Some(array.as_bytes()[index]);
 }
 
 #[must_use]
 pub const fn split_prefix(arg: &str) -> (FlagPrefix, &str) {
-    let (prefix, prefix_len) = match get_byte(arg, 0) {
-        Some(b'/') => (FlagPrefix::Slash, FlagPrefix::SLASH_LEN),
-        Some(b'-') => match get_byte(arg, 0) {
-            Some(b'-') => (FlagPrefix::DashDash, FlagPrefix::DASHDASH_LEN),
-            Some(_) | None => (FlagPrefix::Dash, FlagPrefix::DASH_LEN),
-        },
-        Some(_) | None => (FlagPrefix::Empty, FlagPrefix::EMPTY_LEN),
-    };
+    let prefix = FlagPrefix::of(arg.as_bytes());
+    let prefix_len = prefix.len();
 
     let arg_ptr = unsafe { arg.as_ptr().add(prefix_len as usize) };
     let arg_len = arg.len() - prefix_len as usize;
@@ -131,4 +134,1610 @@ pub enum AnsiCode {
     PositiveText = 27,
 }
 
-// TODO: backwards compatibility tests
+impl core::fmt::Display for AnsiFg {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return write!(f, "\x1b[{}m", *self as u8);
+    }
+}
+
+impl core::fmt::Display for AnsiBg {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return write!(f, "\x1b[{}m", *self as u8);
+    }
+}
+
+impl core::fmt::Display for AnsiCode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return write!(f, "\x1b[{}m", *self as u8);
+    }
+}
+
+/// Declaration of a flag, which can take a value, i.e.: be an option
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct Flag {
+    pub id: &'static str,
+
+    /// Matched by `-x`, with a value given as `-x value` or `-xvalue`
+    pub short: Option<ascii>,
+
+    /// Matched by `--name`, with a value given as `--name value` or `--name=value`
+    pub long: Option<&'static str>,
+
+    /// Also match the long name as `/name`, with a value given as `/name value` or `/name:value`
+    pub slash: bool,
+
+    /// Name of the value the flag takes, if it takes one
+    pub value_name: Option<&'static str>,
+    pub repeatable: bool,
+    pub help: &'static str,
+
+    /// Value of the flag when missing and not set by [`Flag::env`]
+    pub default_value: Option<&'static str>,
+
+    /// Environment variable the value is read from when the flag is missing
+    pub env: Option<&'static str>,
+
+    /// Heading of the help section the flag is listed under
+    pub section: &'static str,
+
+    /// Also accepted by every subcommand, with its matches collected in the top-level [`Matches`]
+    pub global: bool,
+}
+
+impl Flag {
+    pub const DEFAULT_SECTION: &'static str = "Options";
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn new(id: &'static str) -> Self {
+        return Self {
+            id,
+            short: None,
+            long: None,
+            slash: false,
+            value_name: None,
+            repeatable: false,
+            help: "",
+            default_value: None,
+            env: None,
+            section: Self::DEFAULT_SECTION,
+            global: false,
+        };
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn short(mut self, short: ascii) -> Self {
+        self.short = Some(short);
+        return self;
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn long(mut self, long: &'static str) -> Self {
+        self.long = Some(long);
+        return self;
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn slash(mut self) -> Self {
+        self.slash = true;
+        return self;
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn value(mut self, value_name: &'static str) -> Self {
+        self.value_name = Some(value_name);
+        return self;
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn repeatable(mut self) -> Self {
+        self.repeatable = true;
+        return self;
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn help(mut self, help: &'static str) -> Self {
+        self.help = help;
+        return self;
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn default_value(mut self, default_value: &'static str) -> Self {
+        self.default_value = Some(default_value);
+        return self;
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn env(mut self, env: &'static str) -> Self {
+        self.env = Some(env);
+        return self;
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn section(mut self, section: &'static str) -> Self {
+        self.section = section;
+        return self;
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn global(mut self) -> Self {
+        self.global = true;
+        return self;
+    }
+
+    /// Forms the flag can be given in, e.g.: `-o, --out, /out <FILE>`
+    #[must_use]
+    pub fn forms(&self) -> String {
+        let mut forms = String::new();
+        match self.short {
+            Some(short) => {
+                forms.push('-');
+                forms.push(short as char);
+            }
+            None => forms.push_str("    "),
+        }
+        if let Some(long) = self.long {
+            if self.short.is_some() {
+                forms.push_str(", ");
+            }
+            forms.push_str("--");
+            forms.push_str(long);
+            if self.slash {
+                forms.push_str(", /");
+                forms.push_str(long);
+            }
+        }
+        if let Some(value_name) = self.value_name {
+            forms.push_str(" <");
+            forms.push_str(value_name);
+            forms.push('>');
+        }
+        return forms;
+    }
+
+    /// Help message followed by the default value and environment variable, if any
+    #[must_use]
+    pub fn description(&self) -> String {
+        let mut description = self.help.to_owned();
+        if let Some(default_value) = self.default_value {
+            description.push_str(" [default: ");
+            description.push_str(default_value);
+            description.push(']');
+        }
+        if let Some(env) = self.env {
+            description.push_str(" [env: ");
+            description.push_str(env);
+            description.push(']');
+        }
+        return description.trim_start().to_owned();
+    }
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct Positional {
+    pub id: &'static str,
+    pub required: bool,
+
+    /// Collects all the remaining positional arguments
+    pub variadic: bool,
+    pub help: &'static str,
+}
+
+impl Positional {
+    #[must_use]
+    #[inline(always)]
+    pub const fn new(id: &'static str) -> Self {
+        return Self { id, required: false, variadic: false, help: "" };
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn required(mut self) -> Self {
+        self.required = true;
+        return self;
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn variadic(mut self) -> Self {
+        self.variadic = true;
+        return self;
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn help(mut self, help: &'static str) -> Self {
+        self.help = help;
+        return self;
+    }
+
+    /// How the argument is shown in the usage line, e.g.: `<input>` or `[rest]...`
+    #[must_use]
+    pub fn usage(&self) -> String {
+        let (open, close) = if self.required { ('<', '>') } else { ('[', ']') };
+        let ellipsis = if self.variadic { "..." } else { "" };
+        return format!("{open}{}{close}{ellipsis}", self.id);
+    }
+}
+
+/// Bytes of the argument at `index` an error refers to
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub struct ArgSpan {
+    pub index: uoffset,
+    pub span: Span<uoffset>,
+}
+
+impl ArgSpan {
+    #[must_use]
+    #[inline(always)]
+    pub const fn new(index: uoffset, start: uoffset, end: uoffset) -> Self {
+        return Self { index, span: Span { start, end } };
+    }
+}
+
+// tokenizing
+
+/// Kind of a command-line [`Token`]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum TokenKind<'args> {
+    /// `--`, after which every argument is positional
+    Terminator,
+
+    /// Argument without a prefix, `-` alone, and every argument after a [`TokenKind::Terminator`]
+    Positional,
+
+    /// `-x`, possibly bundled with other short flags as in `-abc`
+    Short(ascii),
+
+    /// `--name`
+    Long(&'args OsStr),
+
+    /// `/name`, which could also be an absolute path
+    Slash(&'args OsStr),
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct Token<'args> {
+    pub kind: TokenKind<'args>,
+
+    /// Whole argument the token was found in
+    pub arg: &'args OsStr,
+
+    /// Span of the flag, including its prefix unless bundled, or of the whole argument
+    pub span: ArgSpan,
+
+    /// Value attached to the flag, as in `-O2`, `--name=value` and `/name:value`
+    pub value: Option<&'args OsStr>,
+}
+
+/// Sub-slice of an argument, always split on an ascii character
+#[cfg(unix)]
+#[must_use]
+#[inline(always)]
+fn os_str(bytes: &[u8]) -> &OsStr {
+    return OsStr::from_bytes(bytes);
+}
+
+/// Sub-slice of an argument, always split on an ascii character
+#[cfg(not(unix))]
+#[must_use]
+#[inline(always)]
+fn os_str(bytes: &[u8]) -> &OsStr {
+    // SAFETY: arguments are only ever split right before or after an ascii character
+    return unsafe { OsStr::from_encoded_bytes_unchecked(bytes) };
+}
+
+/// Splits `args` into [`Token`]s, only using `flags` to know which flags take a value
+///
+/// Bundled short flags are split until one taking a value, which takes the rest of the argument as
+/// its value. The argument after a flag taking a value without an attached one is always
+/// positional, as `--` in `-o --`. Arguments need not be valid UTF-8, but only ascii characters are
+/// recognized as short flags, bundles with non-ascii characters before any value are positional
+#[must_use]
+pub fn tokenize<'args, S: AsRef<OsStr>>(args: &'args [S], flags: &[&Flag]) -> Vec<Token<'args>> {
+    let valued_flags: Vec<&Flag> =
+        flags.iter().copied().filter(|flag| return flag.value_name.is_some()).collect();
+    let mut tokens = Vec::with_capacity(args.len());
+    let mut only_positionals = false;
+    let mut value_expected = false;
+
+    for (index, raw_arg) in args.iter().enumerate() {
+        let arg = raw_arg.as_ref();
+        let bytes = arg.as_encoded_bytes();
+        let prefix = if only_positionals || value_expected {
+            FlagPrefix::Empty
+        } else {
+            FlagPrefix::of(bytes)
+        };
+        value_expected = false;
+        let prefix_len = prefix.len() as uoffset;
+        let rest = &bytes[prefix_len..];
+
+        match prefix {
+            FlagPrefix::DashDash if rest.is_empty() => {
+                only_positionals = true;
+                let span = ArgSpan::new(index, 0, bytes.len());
+                tokens.push(Token { kind: TokenKind::Terminator, arg, span, value: None });
+            }
+            FlagPrefix::DashDash | FlagPrefix::Slash => {
+                let separator = if prefix == FlagPrefix::Slash { b':' } else { b'=' };
+                let (name, value) = match rest.iter().position(|&byte| return byte == separator) {
+                    Some(separator_index) => {
+                        (&rest[..separator_index], Some(os_str(&rest[separator_index + 1..])))
+                    }
+                    None => (rest, None),
+                };
+                let kind = if prefix == FlagPrefix::Slash {
+                    TokenKind::Slash(os_str(name))
+                } else {
+                    TokenKind::Long(os_str(name))
+                };
+                value_expected = value.is_none()
+                    && valued_flags.iter().any(|flag| {
+                        let slash_matches = prefix != FlagPrefix::Slash || flag.slash;
+                        return slash_matches
+                            && flag.long.is_some_and(|long| return long.as_bytes() == name);
+                    });
+                let span = ArgSpan::new(index, 0, prefix_len + name.len());
+                tokens.push(Token { kind, arg, span, value });
+            }
+            FlagPrefix::Dash => {
+                let bundle_len = rest
+                    .iter()
+                    .position(|&short| {
+                        return !short.is_ascii()
+                            || valued_flags.iter().any(|flag| return flag.short == Some(short));
+                    })
+                    .unwrap_or(rest.len());
+                if rest.is_empty()
+                    || rest.get(bundle_len).is_some_and(|short| return !short.is_ascii())
+                {
+                    let span = ArgSpan::new(index, 0, bytes.len());
+                    tokens.push(Token { kind: TokenKind::Positional, arg, span, value: None });
+                    continue;
+                }
+
+                for (short_index, &short) in rest.iter().enumerate().take(bundle_len) {
+                    let start = if short_index == 0 { 0 } else { prefix_len + short_index };
+                    let span = ArgSpan::new(index, start, prefix_len + short_index + 1);
+                    tokens.push(Token { kind: TokenKind::Short(short), arg, span, value: None });
+                }
+                if let Some(&short) = rest.get(bundle_len) {
+                    let start = if bundle_len == 0 { 0 } else { prefix_len + bundle_len };
+                    let value_start = prefix_len + bundle_len + 1;
+                    let span = ArgSpan::new(index, start, value_start);
+                    let value =
+                        (value_start < bytes.len()).then(|| return os_str(&bytes[value_start..]));
+                    value_expected = value.is_none();
+                    tokens.push(Token { kind: TokenKind::Short(short), arg, span, value });
+                }
+            }
+            FlagPrefix::Empty => {
+                let span = ArgSpan::new(index, 0, bytes.len());
+                tokens.push(Token { kind: TokenKind::Positional, arg, span, value: None });
+            }
+        }
+    }
+    return tokens;
+}
+
+// parsing
+
+/// Occurrence of a flag or positional argument
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct Match<'args> {
+    pub id: &'static str,
+    pub value: Option<&'args OsStr>,
+
+    /// Span of the value if present, of the flag otherwise
+    pub span: ArgSpan,
+}
+
+/// Flags and positional arguments found while parsing, in the order they were given
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub struct Matches<'args> {
+    pub matches: Vec<Match<'args>>,
+
+    /// Values of the missing flags, read from their environment variable or defaulted
+    pub fallbacks: Vec<Fallback>,
+    pub subcommand: Option<Box<SubcommandMatches<'args>>>,
+}
+
+/// Value of a missing flag, spanning the end of the arguments
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct Fallback {
+    pub id: &'static str,
+    pub value: OsString,
+    pub span: ArgSpan,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct SubcommandMatches<'args> {
+    pub name: &'static str,
+    pub matches: Matches<'args>,
+}
+
+impl Matches<'_> {
+    /// Name and matches of the subcommand given, if any
+    #[must_use]
+    pub fn subcommand(&self) -> Option<(&'static str, &Self)> {
+        let subcommand = self.subcommand.as_deref()?;
+        return Some((subcommand.name, &subcommand.matches));
+    }
+
+    #[must_use]
+    pub fn count(&self, id: &str) -> usize {
+        return self.matches.iter().filter(|found| return found.id == id).count();
+    }
+
+    #[must_use]
+    pub fn is_present(&self, id: &str) -> bool {
+        return self.matches.iter().any(|found| return found.id == id);
+    }
+
+    fn fallback(&self, id: &str) -> Option<&Fallback> {
+        return self.fallbacks.iter().find(|fallback| return fallback.id == id);
+    }
+
+    /// Last value given, or the fallback value if the flag is missing
+    #[must_use]
+    pub fn value(&self, id: &str) -> Option<&OsStr> {
+        let Some(found) = self.matches.iter().rev().find(|found| return found.id == id) else {
+            return self.fallback(id).map(|fallback| return fallback.value.as_os_str());
+        };
+        return found.value;
+    }
+
+    /// Values given, or the fallback value if the flag is missing
+    #[must_use]
+    pub fn values(&self, id: &str) -> Vec<&OsStr> {
+        if !self.is_present(id) {
+            return self
+                .fallback(id)
+                .map(|fallback| return fallback.value.as_os_str())
+                .into_iter()
+                .collect();
+        }
+        return self
+            .matches
+            .iter()
+            .filter(|found| return found.id == id)
+            .filter_map(|found| return found.value)
+            .collect();
+    }
+
+    /// Parses the last value given, or the fallback value if the flag is missing, which must be
+    /// valid UTF-8
+    pub fn parse<T: core::str::FromStr>(&self, id: &str) -> Result<Option<T>, ParseError<'_>> {
+        let last = self.matches.iter().rev().find(|found| return found.id == id);
+        let (flag_id, value, span) = match last {
+            Some(found) => {
+                let Some(value) = found.value else {
+                    return Ok(None);
+                };
+                (found.id, value, found.span)
+            }
+            None => {
+                let Some(fallback) = self.fallback(id) else {
+                    return Ok(None);
+                };
+                (fallback.id, fallback.value.as_os_str(), fallback.span)
+            }
+        };
+        return match value.to_str().map(str::parse) {
+            Some(Ok(parsed)) => Ok(Some(parsed)),
+            Some(Err(_)) | None => Err(ParseError::InvalidValue { id: flag_id, value, span }),
+        };
+    }
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum ParseError<'args> {
+    UnknownFlag { arg: &'args OsStr, span: ArgSpan },
+    MissingValue { id: &'static str, span: ArgSpan },
+    UnexpectedValue { id: &'static str, span: ArgSpan },
+    Repeated { id: &'static str, span: ArgSpan },
+    UnexpectedPositional { arg: &'args OsStr, span: ArgSpan },
+    MissingPositional { id: &'static str, span: ArgSpan },
+    InvalidValue { id: &'static str, value: &'args OsStr, span: ArgSpan },
+    UnknownSubcommand { arg: &'args OsStr, suggestion: Option<&'static str>, span: ArgSpan },
+}
+
+impl ParseError<'_> {
+    #[must_use]
+    #[inline]
+    pub const fn span(&self) -> ArgSpan {
+        return match self {
+            Self::UnknownFlag { span, .. }
+            | Self::MissingValue { span, .. }
+            | Self::UnexpectedValue { span, .. }
+            | Self::Repeated { span, .. }
+            | Self::UnexpectedPositional { span, .. }
+            | Self::MissingPositional { span, .. }
+            | Self::InvalidValue { span, .. }
+            | Self::UnknownSubcommand { span, .. } => *span,
+        };
+    }
+}
+
+impl core::fmt::Display for ParseError<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return match self {
+            Self::UnknownFlag { arg, .. } => write!(f, "unknown flag `{}`", arg.display()),
+            Self::MissingValue { id, .. } => write!(f, "missing value for `{id}`"),
+            Self::UnexpectedValue { id, .. } => write!(f, "`{id}` does not take a value"),
+            Self::Repeated { id, .. } => write!(f, "`{id}` can only be given once"),
+            Self::UnexpectedPositional { arg, .. } => {
+                write!(f, "unexpected argument `{}`", arg.display())
+            }
+            Self::MissingPositional { id, .. } => write!(f, "missing required argument `{id}`"),
+            Self::InvalidValue { id, value, .. } => {
+                write!(f, "invalid value `{}` for `{id}`", value.display())
+            }
+            Self::UnknownSubcommand { arg, suggestion: None, .. } => {
+                write!(f, "unknown subcommand `{}`", arg.display())
+            }
+            Self::UnknownSubcommand { arg, suggestion: Some(suggestion), .. } => {
+                write!(f, "unknown subcommand `{}`, did you mean `{suggestion}`?", arg.display())
+            }
+        };
+    }
+}
+
+#[expect(clippy::missing_trait_methods, reason = "the default implementations are fine")]
+impl core::error::Error for ParseError<'_> {}
+
+/// Levenshtein distance between `from` and `to`, in characters
+#[must_use]
+pub fn edit_distance(from: &str, to: &str) -> usize {
+    let to_chars: Vec<utf32> = to.chars().collect();
+    let mut previous: Vec<usize> = (0..=to_chars.len()).collect();
+    let mut current = vec![0; to_chars.len() + 1];
+    for (from_index, from_char) in from.chars().enumerate() {
+        current[0] = from_index + 1;
+        for (to_index, &to_char) in to_chars.iter().enumerate() {
+            let substitution = previous[to_index] + usize::from(from_char != to_char);
+            let deletion = previous[to_index + 1] + 1;
+            let insertion = current[to_index] + 1;
+            current[to_index + 1] = substitution.min(deletion).min(insertion);
+        }
+        core::mem::swap(&mut previous, &mut current);
+    }
+    return previous[to_chars.len()];
+}
+
+/// Declaration of the flags, positional arguments and subcommands of a command
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub struct Command {
+    pub name: &'static str,
+    pub about: &'static str,
+    pub flags: Vec<Flag>,
+    pub positionals: Vec<Positional>,
+    pub subcommands: Vec<Command>,
+}
+
+impl Command {
+    #[must_use]
+    #[inline(always)]
+    pub const fn new(name: &'static str) -> Self {
+        return Self {
+            name,
+            about: "",
+            flags: Vec::new(),
+            positionals: Vec::new(),
+            subcommands: Vec::new(),
+        };
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn about(mut self, about: &'static str) -> Self {
+        self.about = about;
+        return self;
+    }
+
+    #[must_use]
+    pub fn flag(mut self, flag: Flag) -> Self {
+        self.flags.push(flag);
+        return self;
+    }
+
+    #[must_use]
+    pub fn positional(mut self, positional: Positional) -> Self {
+        self.positionals.push(positional);
+        return self;
+    }
+
+    #[must_use]
+    pub fn subcommand(mut self, subcommand: Self) -> Self {
+        self.subcommands.push(subcommand);
+        return self;
+    }
+
+    #[must_use]
+    pub fn find_subcommand(&self, name: &str) -> Option<&Self> {
+        return self.subcommands.iter().find(|subcommand| return subcommand.name == name);
+    }
+
+    /// Subcommand closest to the unknown `name`, if close enough to likely be a typo
+    #[must_use]
+    pub fn suggest_subcommand(&self, name: &str) -> Option<&'static str> {
+        let max_distance = name.chars().count() / 3 + 1;
+        return self
+            .subcommands
+            .iter()
+            .map(|subcommand| return (edit_distance(name, subcommand.name), subcommand.name))
+            .filter(|&(distance, _)| return distance <= max_distance)
+            .min_by_key(|&(distance, _)| return distance)
+            .map(|(_, subcommand_name)| return subcommand_name);
+    }
+
+    /// Parses `args`, which should not include the program name, the indices of the returned
+    /// [`ArgSpan`]s are relative to `args`
+    ///
+    /// Arguments after a `--` are always positional, as are slash-prefixed arguments not matching
+    /// any flag, since they could be paths. The first positional argument selects a subcommand,
+    /// which parses the remaining arguments
+    pub fn parse<'args, S: AsRef<OsStr>>(
+        &self,
+        args: &'args [S],
+    ) -> Result<Matches<'args>, ParseError<'args>> {
+        let mut globals = Matches::default();
+        let mut matches = self.parse_from(args, 0, &[], &mut globals)?;
+        matches.matches.append(&mut globals.matches);
+        matches.fallbacks.append(&mut globals.fallbacks);
+        return Ok(matches);
+    }
+
+    /// Parses the arguments starting at `start`, collecting the matches of the global flags, both
+    /// declared by this command and `inherited`, in `globals`. Missing flags declared by this
+    /// command fall back to their environment variable, then to their default value
+    fn parse_from<'command, 'args, S: AsRef<OsStr>>(
+        &'command self,
+        args: &'args [S],
+        start: uoffset,
+        inherited: &[&'command Flag],
+        globals: &mut Matches<'args>,
+    ) -> Result<Matches<'args>, ParseError<'args>> {
+        let mut flags: Vec<&Flag> = self.flags.iter().collect();
+        flags.extend_from_slice(inherited);
+
+        let mut tokens = tokenize(&args[start..], &flags);
+        for token in &mut tokens {
+            token.span.index += start;
+        }
+
+        let mut matches = Matches::default();
+        let mut positional_index = 0;
+        let mut after_terminator = false;
+        let mut token_index = 0;
+        while let Some(token) = tokens.get(token_index) {
+            token_index += 1;
+            let found_flag = match token.kind {
+                TokenKind::Terminator => {
+                    after_terminator = true;
+                    continue;
+                }
+                TokenKind::Positional => None,
+                TokenKind::Short(short) => {
+                    let flag_index = flags.iter().position(|flag| return flag.short == Some(short));
+                    if flag_index.is_none() {
+                        return Err(ParseError::UnknownFlag { arg: token.arg, span: token.span });
+                    }
+                    flag_index
+                }
+                TokenKind::Long(name) => {
+                    let flag_index = flags
+                        .iter()
+                        .position(|flag| return flag.long.is_some_and(|long| return name == long));
+                    if flag_index.is_none() {
+                        return Err(ParseError::UnknownFlag { arg: token.arg, span: token.span });
+                    }
+                    flag_index
+                }
+                TokenKind::Slash(name) => flags.iter().position(|flag| {
+                    return flag.slash && flag.long.is_some_and(|long| return name == long);
+                }),
+            };
+
+            let Some(flag_index) = found_flag else {
+                let span = ArgSpan::new(token.span.index, 0, token.arg.len());
+                let selects_subcommand = token.kind == TokenKind::Positional
+                    && !after_terminator
+                    && positional_index == 0
+                    && !self.subcommands.is_empty();
+                if selects_subcommand {
+                    let found_subcommand = self
+                        .subcommands
+                        .iter()
+                        .find(|subcommand| return token.arg == subcommand.name);
+                    if let Some(subcommand) = found_subcommand {
+                        let mut subcommand_inherited = inherited.to_vec();
+                        subcommand_inherited
+                            .extend(self.flags.iter().filter(|flag| return flag.global));
+                        let subcommand_matches = subcommand.parse_from(
+                            args,
+                            token.span.index + 1,
+                            &subcommand_inherited,
+                            globals,
+                        )?;
+                        matches.subcommand = Some(Box::new(SubcommandMatches {
+                            name: subcommand.name,
+                            matches: subcommand_matches,
+                        }));
+                        break;
+                    }
+
+                    if self.positionals.is_empty() {
+                        let suggestion = token
+                            .arg
+                            .to_str()
+                            .and_then(|name| return self.suggest_subcommand(name));
+                        return Err(ParseError::UnknownSubcommand {
+                            arg: token.arg,
+                            suggestion,
+                            span,
+                        });
+                    }
+                }
+
+                let Some(positional) = self.positionals.get(positional_index) else {
+                    return Err(ParseError::UnexpectedPositional { arg: token.arg, span });
+                };
+                matches.matches.push(Match { id: positional.id, value: Some(token.arg), span });
+                if !positional.variadic {
+                    positional_index += 1;
+                }
+                continue;
+            };
+
+            let flag = flags[flag_index];
+            let previous_matches = if flag.global { &globals.matches } else { &matches.matches };
+            if !flag.repeatable && previous_matches.iter().any(|found| return found.id == flag.id) {
+                return Err(ParseError::Repeated { id: flag.id, span: token.span });
+            }
+
+            let arg_len = token.arg.len();
+            let found = match (flag.value_name, token.value) {
+                (None, None) => Match { id: flag.id, value: None, span: token.span },
+                (None, Some(_)) => {
+                    let value_span = ArgSpan::new(token.span.index, token.span.span.end, arg_len);
+                    return Err(ParseError::UnexpectedValue { id: flag.id, span: value_span });
+                }
+                (Some(_), Some(value)) => {
+                    let value_span = ArgSpan::new(token.span.index, arg_len - value.len(), arg_len);
+                    Match { id: flag.id, value: Some(value), span: value_span }
+                }
+                (Some(_), None) => {
+                    let value_index = token.span.index + 1;
+                    let Some(next_arg) = args.get(value_index) else {
+                        return Err(ParseError::MissingValue { id: flag.id, span: token.span });
+                    };
+                    let value = next_arg.as_ref();
+                    while tokens
+                        .get(token_index)
+                        .is_some_and(|next| return next.span.index == value_index)
+                    {
+                        token_index += 1;
+                    }
+                    let value_span = ArgSpan::new(value_index, 0, value.len());
+                    Match { id: flag.id, value: Some(value), span: value_span }
+                }
+            };
+            if flag.global {
+                globals.matches.push(found);
+            } else {
+                matches.matches.push(found);
+            }
+        }
+
+        let end = ArgSpan::new(args.len(), 0, 0);
+        for positional in &self.positionals {
+            if positional.required && !matches.is_present(positional.id) {
+                return Err(ParseError::MissingPositional { id: positional.id, span: end });
+            }
+        }
+
+        for flag in self.flags.iter().filter(|flag| return flag.value_name.is_some()) {
+            let flag_matches = if flag.global { &mut *globals } else { &mut matches };
+            if flag_matches.is_present(flag.id) {
+                continue;
+            }
+            let fallback = flag
+                .env
+                .and_then(std::env::var_os)
+                .or_else(|| return flag.default_value.map(OsString::from));
+            if let Some(value) = fallback {
+                flag_matches.fallbacks.push(Fallback { id: flag.id, value, span: end });
+            }
+        }
+        return Ok(matches);
+    }
+}
+
+// help
+
+/// Width of the terminal, as set by the `COLUMNS` environment variable or as reported for the
+/// standard output on Linux, defaulting to [`HelpFormat::DEFAULT_WIDTH`]
+#[must_use]
+pub fn terminal_width() -> usize {
+    let columns = std::env::var("COLUMNS").ok().and_then(|columns| return columns.parse().ok());
+    if let Some(width) = columns.filter(|&width: &usize| return width > 0) {
+        return width;
+    }
+
+    #[cfg(all(
+        target_os = "linux",
+        any(
+            target_arch = "x86",
+            target_arch = "x86_64",
+            target_arch = "arm",
+            target_arch = "aarch64"
+        )
+    ))]
+    {
+        let mut size = terminal::WinSize::default();
+        // SAFETY: TIOCGWINSZ only writes a `WinSize` to the given pointer
+        let result = unsafe {
+            terminal::ioctl(terminal::STDOUT_FILENO, terminal::TIOCGWINSZ, &raw mut size)
+        };
+        if result == 0 && size.columns > 0 {
+            return size.columns as usize;
+        }
+    }
+
+    return HelpFormat::DEFAULT_WIDTH;
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86", target_arch = "x86_64", target_arch = "arm", target_arch = "aarch64")
+))]
+mod terminal {
+    use core::ffi::{c_int, c_ulong, c_ushort};
+
+    pub(super) const STDOUT_FILENO: c_int = 1;
+    pub(super) const TIOCGWINSZ: c_ulong = 0x5413;
+
+    #[derive(Clone, Copy, Debug, Default)]
+    #[repr(C)]
+    pub(super) struct WinSize {
+        pub(super) rows: c_ushort,
+        pub(super) columns: c_ushort,
+        pub(super) x_pixels: c_ushort,
+        pub(super) y_pixels: c_ushort,
+    }
+
+    unsafe extern "C" {
+        pub(super) fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+    }
+}
+
+/// Layout of the help generated by [`Command::write_help`]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct HelpFormat {
+    pub colored: bool,
+
+    /// Columns descriptions are wrapped to
+    pub width: usize,
+}
+
+impl Default for HelpFormat {
+    #[inline(always)]
+    fn default() -> Self {
+        return Self::PLAIN;
+    }
+}
+
+impl HelpFormat {
+    pub const DEFAULT_WIDTH: usize = 80;
+
+    /// Narrowest width descriptions are wrapped to, to avoid a word per line on narrow terminals
+    pub const MIN_DESCRIPTION_WIDTH: usize = 20;
+
+    pub const PLAIN: Self = Self { colored: false, width: Self::DEFAULT_WIDTH };
+    pub const COLORED: Self = Self { colored: true, width: Self::DEFAULT_WIDTH };
+
+    const INDENT: usize = 2;
+    const GAP: usize = 2;
+
+    /// Wraps descriptions to the [`terminal_width`]
+    #[must_use]
+    pub fn detect(colored: bool) -> Self {
+        return Self { colored, width: terminal_width() };
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn with_width(mut self, width: usize) -> Self {
+        self.width = width;
+        return self;
+    }
+
+    fn write_heading<W: Write>(self, out: &mut W, heading: &str) -> core::fmt::Result {
+        if !self.colored {
+            return write!(out, "{heading}:");
+        }
+        return write!(
+            out,
+            "{}{}{heading}:{}",
+            AnsiCode::Bold,
+            AnsiFg::LightGreen,
+            AnsiCode::Default
+        );
+    }
+
+    /// Writes each usage or forms of an argument followed by its description, aligned and wrapped
+    fn write_entries<W: Write>(
+        self,
+        out: &mut W,
+        entries: &[(String, String)],
+    ) -> core::fmt::Result {
+        let usage_width =
+            entries.iter().map(|(usage, _)| return usage.chars().count()).max().unwrap_or(0);
+        let column = Self::INDENT + usage_width + Self::GAP;
+        let description_width = self.width.saturating_sub(column).max(Self::MIN_DESCRIPTION_WIDTH);
+
+        for (usage, description) in entries {
+            write!(out, "{:indent$}", "", indent = Self::INDENT)?;
+            if self.colored {
+                write!(out, "{}{}{usage}{}", AnsiCode::Bold, AnsiFg::LightCyan, AnsiCode::Default)?;
+            } else {
+                out.write_str(usage)?;
+            }
+            if description.is_empty() {
+                writeln!(out)?;
+                continue;
+            }
+
+            let padding = usage_width - usage.chars().count() + Self::GAP;
+            write!(out, "{:padding$}", "")?;
+            let mut line_width = 0;
+            for word in description.split_whitespace() {
+                let word_width = word.chars().count();
+                if line_width == 0 {
+                    // first word of the line, written even if it doesn't fit
+                } else if line_width + 1 + word_width > description_width {
+                    writeln!(out)?;
+                    write!(out, "{:column$}", "")?;
+                    line_width = 0;
+                } else {
+                    out.write_char(' ')?;
+                    line_width += 1;
+                }
+                out.write_str(word)?;
+                line_width += word_width;
+            }
+            writeln!(out)?;
+        }
+        return Ok(());
+    }
+}
+
+impl Command {
+    /// Writes `Usage: name [OPTIONS] <positionals>... <COMMAND>`
+    pub fn write_usage<W: Write>(&self, out: &mut W, format: HelpFormat) -> core::fmt::Result {
+        return self.help(format).write_usage(out);
+    }
+
+    /// Writes the description of the command, its usage, its subcommands, its arguments and its
+    /// flags grouped by [`Flag::section`], in the order they were declared
+    pub fn write_help<W: Write>(&self, out: &mut W, format: HelpFormat) -> core::fmt::Result {
+        return self.help(format).write(out);
+    }
+
+    #[must_use]
+    pub fn help(&self, format: HelpFormat) -> Help<'_> {
+        return Help { command: self, names: vec![self.name], inherited: Vec::new(), format };
+    }
+
+    /// Help of the subcommand reached by following the names in `path`, also listing the global
+    /// flags it inherits
+    #[must_use]
+    pub fn subcommand_help(&self, path: &[&str], format: HelpFormat) -> Option<Help<'_>> {
+        let mut help = self.help(format);
+        for name in path {
+            help.inherited.extend(help.command.flags.iter().filter(|flag| return flag.global));
+            help.command = help.command.find_subcommand(name)?;
+            help.names.push(help.command.name);
+        }
+        return Some(help);
+    }
+}
+
+/// Help of a [`Command`], as written by [`Command::write_help`]
+#[derive(Clone, Debug)]
+pub struct Help<'command> {
+    pub command: &'command Command,
+
+    /// Names of the command and of its parent commands, starting from the top-level one
+    pub names: Vec<&'static str>,
+
+    /// Global flags inherited from the parent commands
+    pub inherited: Vec<&'command Flag>,
+    pub format: HelpFormat,
+}
+
+impl Help<'_> {
+    pub fn write_usage<W: Write>(&self, out: &mut W) -> core::fmt::Result {
+        self.format.write_heading(out, "Usage")?;
+        for name in &self.names {
+            write!(out, " {name}")?;
+        }
+        if !self.command.flags.is_empty() || !self.inherited.is_empty() {
+            out.write_str(" [OPTIONS]")?;
+        }
+        for positional in &self.command.positionals {
+            write!(out, " {}", positional.usage())?;
+        }
+        if !self.command.subcommands.is_empty() {
+            out.write_str(" <COMMAND>")?;
+        }
+        return writeln!(out);
+    }
+
+    pub fn write<W: Write>(&self, out: &mut W) -> core::fmt::Result {
+        let command = self.command;
+        if !command.about.is_empty() {
+            writeln!(out, "{}", command.about)?;
+            writeln!(out)?;
+        }
+        self.write_usage(out)?;
+
+        if !command.subcommands.is_empty() {
+            let entries: Vec<(String, String)> = command
+                .subcommands
+                .iter()
+                .map(|subcommand| return (subcommand.name.to_owned(), subcommand.about.to_owned()))
+                .collect();
+            writeln!(out)?;
+            self.format.write_heading(out, "Commands")?;
+            writeln!(out)?;
+            self.format.write_entries(out, &entries)?;
+        }
+
+        if !command.positionals.is_empty() {
+            let entries: Vec<(String, String)> = command
+                .positionals
+                .iter()
+                .map(|positional| return (positional.usage(), positional.help.to_owned()))
+                .collect();
+            writeln!(out)?;
+            self.format.write_heading(out, "Arguments")?;
+            writeln!(out)?;
+            self.format.write_entries(out, &entries)?;
+        }
+
+        let flags: Vec<&Flag> =
+            command.flags.iter().chain(self.inherited.iter().copied()).collect();
+        let mut sections: Vec<&'static str> = Vec::new();
+        for flag in &flags {
+            if !sections.contains(&flag.section) {
+                sections.push(flag.section);
+            }
+        }
+        for section in sections {
+            let entries: Vec<(String, String)> = flags
+                .iter()
+                .filter(|flag| return flag.section == section)
+                .map(|flag| return (flag.forms(), flag.description()))
+                .collect();
+            writeln!(out)?;
+            self.format.write_heading(out, section)?;
+            writeln!(out)?;
+            self.format.write_entries(out, &entries)?;
+        }
+        return Ok(());
+    }
+}
+
+impl core::fmt::Display for Help<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return self.write(f);
+    }
+}
+
+#[expect(clippy::unwrap_used)]
+#[cfg(test)]
+#[rustfmt::skip]
+mod tests {
+    mod _0_1_2_functionality {
+        use crate::cli::{
+            AnsiCode, AnsiFg, ArgSpan, Command, Flag, FlagPrefix, HelpFormat, ParseError, Positional, Token, TokenKind,
+            edit_distance, split_prefix, tokenize,
+        };
+        use std::ffi::OsStr;
+
+        fn token<'args>(kind: TokenKind<'args>, arg: &'args str, span: ArgSpan, value: Option<&'args str>) -> Token<'args> {
+            return Token { kind, arg: OsStr::new(arg), span, value: value.map(OsStr::new) };
+        }
+
+        #[test]
+        fn split_prefixes() {
+            assert_eq!(split_prefix(""), (FlagPrefix::Empty, ""));
+            assert_eq!(split_prefix("file"), (FlagPrefix::Empty, "file"));
+            assert_eq!(split_prefix("-"), (FlagPrefix::Dash, ""));
+            assert_eq!(split_prefix("-h"), (FlagPrefix::Dash, "h"));
+            assert_eq!(split_prefix("-h-"), (FlagPrefix::Dash, "h-"));
+            assert_eq!(split_prefix("--"), (FlagPrefix::DashDash, ""));
+            assert_eq!(split_prefix("--help"), (FlagPrefix::DashDash, "help"));
+            assert_eq!(split_prefix("---"), (FlagPrefix::DashDash, "-"));
+            assert_eq!(split_prefix("/"), (FlagPrefix::Slash, ""));
+            assert_eq!(split_prefix("/help"), (FlagPrefix::Slash, "help"));
+            assert_eq!(split_prefix("/-h"), (FlagPrefix::Slash, "-h"));
+            assert_eq!(split_prefix("\u{e9}-"), (FlagPrefix::Empty, "\u{e9}-"));
+        }
+
+        #[test]
+        fn tokenize_empty_prefix() {
+            let args = ["file", "", "\u{e9}"];
+            assert_eq!(tokenize(&args, &[]), [
+                token(TokenKind::Positional, "file", ArgSpan::new(0, 0, 4), None),
+                token(TokenKind::Positional, "", ArgSpan::new(1, 0, 0), None),
+                token(TokenKind::Positional, "\u{e9}", ArgSpan::new(2, 0, 2), None),
+            ]);
+        }
+
+        #[test]
+        fn tokenize_dash_prefix() {
+            let level = Flag::new("level").short(b'O').value("LEVEL");
+            let args = ["-", "-a", "-abc", "-O2", "-aO2", "-a\u{e9}", "-\u{e9}", "-aO\u{e9}"];
+            assert_eq!(tokenize(&args, &[&level]), [
+                token(TokenKind::Positional, "-", ArgSpan::new(0, 0, 1), None),
+                token(TokenKind::Short(b'a'), "-a", ArgSpan::new(1, 0, 2), None),
+                token(TokenKind::Short(b'a'), "-abc", ArgSpan::new(2, 0, 2), None),
+                token(TokenKind::Short(b'b'), "-abc", ArgSpan::new(2, 2, 3), None),
+                token(TokenKind::Short(b'c'), "-abc", ArgSpan::new(2, 3, 4), None),
+                token(TokenKind::Short(b'O'), "-O2", ArgSpan::new(3, 0, 2), Some("2")),
+                token(TokenKind::Short(b'a'), "-aO2", ArgSpan::new(4, 0, 2), None),
+                token(TokenKind::Short(b'O'), "-aO2", ArgSpan::new(4, 2, 3), Some("2")),
+                token(TokenKind::Positional, "-a\u{e9}", ArgSpan::new(5, 0, 4), None),
+                token(TokenKind::Positional, "-\u{e9}", ArgSpan::new(6, 0, 3), None),
+                token(TokenKind::Short(b'a'), "-aO\u{e9}", ArgSpan::new(7, 0, 2), None),
+                token(TokenKind::Short(b'O'), "-aO\u{e9}", ArgSpan::new(7, 2, 3), Some("\u{e9}")),
+            ]);
+
+            let trailing = ["-O"];
+            assert_eq!(tokenize(&trailing, &[&level]), [token(TokenKind::Short(b'O'), "-O", ArgSpan::new(0, 0, 2), None)]);
+
+            let separate = ["-aO", "--", "-a"];
+            assert_eq!(tokenize(&separate, &[&level]), [
+                token(TokenKind::Short(b'a'), "-aO", ArgSpan::new(0, 0, 2), None),
+                token(TokenKind::Short(b'O'), "-aO", ArgSpan::new(0, 2, 3), None),
+                token(TokenKind::Positional, "--", ArgSpan::new(1, 0, 2), None),
+                token(TokenKind::Short(b'a'), "-a", ArgSpan::new(2, 0, 2), None),
+            ]);
+        }
+
+        #[test]
+        fn tokenize_dashdash_prefix() {
+            let args = ["--help", "--out=a.out", "--x=", "--=y", "---", "--", "--help", "-a", "/x"];
+            assert_eq!(tokenize(&args, &[]), [
+                token(TokenKind::Long(OsStr::new("help")), "--help", ArgSpan::new(0, 0, 6), None),
+                token(TokenKind::Long(OsStr::new("out")), "--out=a.out", ArgSpan::new(1, 0, 5), Some("a.out")),
+                token(TokenKind::Long(OsStr::new("x")), "--x=", ArgSpan::new(2, 0, 3), Some("")),
+                token(TokenKind::Long(OsStr::new("")), "--=y", ArgSpan::new(3, 0, 2), Some("y")),
+                token(TokenKind::Long(OsStr::new("-")), "---", ArgSpan::new(4, 0, 3), None),
+                token(TokenKind::Terminator, "--", ArgSpan::new(5, 0, 2), None),
+                token(TokenKind::Positional, "--help", ArgSpan::new(6, 0, 6), None),
+                token(TokenKind::Positional, "-a", ArgSpan::new(7, 0, 2), None),
+                token(TokenKind::Positional, "/x", ArgSpan::new(8, 0, 2), None),
+            ]);
+        }
+
+        #[test]
+        fn tokenize_slash_prefix() {
+            let args = ["/", "/help", "/out:a.out", "/tmp/main.btf", "/x=y"];
+            assert_eq!(tokenize(&args, &[]), [
+                token(TokenKind::Slash(OsStr::new("")), "/", ArgSpan::new(0, 0, 1), None),
+                token(TokenKind::Slash(OsStr::new("help")), "/help", ArgSpan::new(1, 0, 5), None),
+                token(TokenKind::Slash(OsStr::new("out")), "/out:a.out", ArgSpan::new(2, 0, 4), Some("a.out")),
+                token(TokenKind::Slash(OsStr::new("tmp/main.btf")), "/tmp/main.btf", ArgSpan::new(3, 0, 13), None),
+                token(TokenKind::Slash(OsStr::new("x=y")), "/x=y", ArgSpan::new(4, 0, 4), None),
+            ]);
+
+            let out = Flag::new("out").long("out").slash().value("FILE");
+            let level = Flag::new("level").long("level").value("LEVEL");
+            let separate = ["/out", "/help", "/level", "--", "--level", "/out"];
+            assert_eq!(tokenize(&separate, &[&out, &level]), [
+                token(TokenKind::Slash(OsStr::new("out")), "/out", ArgSpan::new(0, 0, 4), None),
+                token(TokenKind::Positional, "/help", ArgSpan::new(1, 0, 5), None),
+                token(TokenKind::Slash(OsStr::new("level")), "/level", ArgSpan::new(2, 0, 6), None),
+                token(TokenKind::Terminator, "--", ArgSpan::new(3, 0, 2), None),
+                token(TokenKind::Positional, "--level", ArgSpan::new(4, 0, 7), None),
+                token(TokenKind::Positional, "/out", ArgSpan::new(5, 0, 4), None),
+            ]);
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn tokenize_non_utf8() {
+            use std::os::unix::ffi::OsStrExt as _;
+
+            let invalid = OsStr::from_bytes(b"\xFFfile");
+            let long = OsStr::from_bytes(b"--out=\xFF");
+            let short = OsStr::from_bytes(b"-a\xFF");
+            let valued_short = OsStr::from_bytes(b"-O\xFF");
+            let args = [invalid, long, short, valued_short];
+            let level = Flag::new("level").short(b'O').value("LEVEL");
+            assert_eq!(tokenize(&args, &[&level]), [
+                Token { kind: TokenKind::Positional, arg: invalid, span: ArgSpan::new(0, 0, 5), value: None },
+                Token { kind: TokenKind::Long(OsStr::new("out")), arg: long, span: ArgSpan::new(1, 0, 5), value: Some(OsStr::from_bytes(b"\xFF")) },
+                Token { kind: TokenKind::Positional, arg: short, span: ArgSpan::new(2, 0, 3), value: None },
+                Token { kind: TokenKind::Short(b'O'), arg: valued_short, span: ArgSpan::new(3, 0, 2), value: Some(OsStr::from_bytes(b"\xFF")) },
+            ]);
+
+            let command = Command::new("test").flag(Flag::new("out").long("out").value("FILE")).positional(Positional::new("input"));
+            let matches = command.parse(&args[..2]).unwrap();
+            assert_eq!(matches.value("input"), Some(invalid));
+            assert_eq!(matches.value("out"), Some(OsStr::from_bytes(b"\xFF")));
+            assert_eq!(
+                matches.parse::<u8>("out"),
+                Err(ParseError::InvalidValue { id: "out", value: OsStr::from_bytes(b"\xFF"), span: ArgSpan::new(1, 6, 7) })
+            );
+        }
+
+        fn command() -> Command {
+            return Command::new("test")
+                .flag(Flag::new("verbose").short(b'v').long("verbose").repeatable())
+                .flag(Flag::new("out").short(b'o').long("out").slash().value("FILE"))
+                .flag(Flag::new("level").short(b'O').long("opt-level").value("LEVEL"))
+                .flag(Flag::new("check").long("check").slash())
+                .positional(Positional::new("input").required())
+                .positional(Positional::new("rest").variadic());
+        }
+
+        #[test]
+        fn parse_args() {
+            let command = command();
+
+            let args = ["-v", "--out", "a.out", "main.btf", "--verbose", "-O2", "--", "--check", "-v"];
+            let matches = command.parse(&args).unwrap();
+            assert_eq!(matches.count("verbose"), 2);
+            assert_eq!(matches.value("out"), Some(OsStr::new("a.out")));
+            assert_eq!(matches.parse::<u8>("level"), Ok(Some(2)));
+            assert_eq!(matches.value("input"), Some(OsStr::new("main.btf")));
+            assert_eq!(matches.values("rest"), [OsStr::new("--check"), OsStr::new("-v")]);
+            assert!(!matches.is_present("check"), "arguments after `--` should be positional");
+
+            let dashdash_value = ["-o", "--", "--verbose", "main.btf"];
+            let dashdash_matches = command.parse(&dashdash_value).unwrap();
+            assert_eq!(dashdash_matches.value("out"), Some(OsStr::new("--")));
+            assert!(dashdash_matches.is_present("verbose"), "`--` was the value of `-o`");
+            assert_eq!(dashdash_matches.value("input"), Some(OsStr::new("main.btf")));
+        }
+
+        #[test]
+        fn parse_attached_values() {
+            let command = command();
+
+            let args = ["--out=a.out", "/check", "/tmp/main.btf", "-"];
+            let matches = command.parse(&args).unwrap();
+            assert_eq!(matches.value("out"), Some(OsStr::new("a.out")));
+            assert!(matches.is_present("check"), "`/check` should match the slash-style flag");
+            assert_eq!(matches.value("input"), Some(OsStr::new("/tmp/main.btf")));
+            assert_eq!(matches.values("rest"), [OsStr::new("-")]);
+        }
+
+        #[test]
+        fn parse_bundled_flags() {
+            let args = ["-vvO2", "-vo", "a.out", "main.btf"];
+            let matches = command().parse(&args).unwrap();
+            assert_eq!(matches.count("verbose"), 3);
+            assert_eq!(matches.value("level"), Some(OsStr::new("2")));
+            assert_eq!(matches.value("out"), Some(OsStr::new("a.out")));
+            assert_eq!(matches.value("input"), Some(OsStr::new("main.btf")));
+
+            assert_eq!(
+                command().parse(&["-vx", "main.btf"]),
+                Err(ParseError::UnknownFlag { arg: OsStr::new("-vx"), span: ArgSpan::new(0, 2, 3) })
+            );
+        }
+
+        #[test]
+        fn parse_slash_values() {
+            let args = ["/out:a.out", "main.btf"];
+            let matches = command().parse(&args).unwrap();
+            assert_eq!(matches.value("out"), Some(OsStr::new("a.out")));
+            assert_eq!(matches.matches[0].span, ArgSpan::new(0, 5, 10));
+        }
+
+        #[test]
+        fn parse_fallbacks() {
+            let command = Command::new("test")
+                .flag(Flag::new("out").short(b'o').value("FILE").default_value("a.out"))
+                .flag(Flag::new("level").short(b'O').value("LEVEL").env("BTF_TEST_UNSET_LEVEL").default_value("1"))
+                .flag(Flag::new("name").long("name").value("NAME").env("CARGO_PKG_NAME").default_value("unnamed"))
+                .flag(Flag::new("color").long("color").value("WHEN").global().default_value("auto"))
+                .subcommand(Command::new("build").flag(Flag::new("target").long("target").value("TARGET").default_value("native")));
+
+            let matches = command.parse(&["-o", "main", "--name=btf"]).unwrap();
+            assert_eq!(matches.value("out"), Some(OsStr::new("main")));
+            assert_eq!(matches.values("out"), [OsStr::new("main")]);
+            assert_eq!(matches.value("name"), Some(OsStr::new("btf")));
+            assert_eq!(matches.parse::<u8>("level"), Ok(Some(1)), "an unset environment variable should fall back to the default");
+            assert!(!matches.is_present("level"), "fallbacks are not given on the command line");
+
+            let missing = command.parse::<&str>(&[]).unwrap();
+            assert_eq!(missing.value("out"), Some(OsStr::new("a.out")));
+            assert_eq!(missing.values("out"), [OsStr::new("a.out")]);
+            assert_eq!(missing.value("name"), Some(OsStr::new(env!("CARGO_PKG_NAME"))), "the environment variable should take precedence");
+            assert_eq!(
+                missing.parse::<u8>("out"),
+                Err(ParseError::InvalidValue { id: "out", value: OsStr::new("a.out"), span: ArgSpan::new(0, 0, 0) })
+            );
+
+            let build_args = ["build"];
+            let build_matches = command.parse(&build_args).unwrap();
+            assert_eq!(build_matches.value("color"), Some(OsStr::new("auto")));
+            let (_, build) = build_matches.subcommand().unwrap();
+            assert_eq!(build.value("target"), Some(OsStr::new("native")));
+            assert_eq!(build.value("color"), None, "global fallbacks should be collected in the top-level matches");
+
+            let color_args = ["build", "--color=never"];
+            let color_matches = command.parse(&color_args).unwrap();
+            assert_eq!(color_matches.value("color"), Some(OsStr::new("never")));
+            assert!(color_matches.fallbacks.iter().all(|fallback| return fallback.id != "color"), "given global flags should not fall back");
+        }
+
+        #[test]
+        fn parse_errors() {
+            let command = command();
+
+            assert_eq!(
+                command.parse(&["--unknown=1", "main.btf"]),
+                Err(ParseError::UnknownFlag { arg: OsStr::new("--unknown=1"), span: ArgSpan::new(0, 0, 9) })
+            );
+            assert_eq!(
+                command.parse(&["main.btf", "-x"]),
+                Err(ParseError::UnknownFlag { arg: OsStr::new("-x"), span: ArgSpan::new(1, 0, 2) })
+            );
+            assert_eq!(
+                command.parse(&["main.btf", "--out"]),
+                Err(ParseError::MissingValue { id: "out", span: ArgSpan::new(1, 0, 5) })
+            );
+            assert_eq!(
+                command.parse(&["--check=yes", "main.btf"]),
+                Err(ParseError::UnexpectedValue { id: "check", span: ArgSpan::new(0, 7, 11) })
+            );
+            assert_eq!(
+                command.parse(&["-o", "a", "main.btf", "/out:b"]),
+                Err(ParseError::Repeated { id: "out", span: ArgSpan::new(3, 0, 4) })
+            );
+            assert_eq!(
+                command.parse(&["-v"]),
+                Err(ParseError::MissingPositional { id: "input", span: ArgSpan::new(1, 0, 0) })
+            );
+            assert_eq!(
+                Command::new("test").parse(&["main.btf"]),
+                Err(ParseError::UnexpectedPositional { arg: OsStr::new("main.btf"), span: ArgSpan::new(0, 0, 8) })
+            );
+
+            let args = ["-Ofast", "main.btf"];
+            let matches = command.parse(&args).unwrap();
+            let error = matches.parse::<u8>("level").unwrap_err();
+            assert_eq!(error, ParseError::InvalidValue { id: "level", value: OsStr::new("fast"), span: ArgSpan::new(0, 2, 6) });
+            assert_eq!(error.to_string(), "invalid value `fast` for `level`");
+        }
+
+        fn help_command() -> Command {
+            return Command::new("btf")
+                .about("Compiler for the back-to-front language")
+                .positional(Positional::new("input").required().help("Source file to compile"))
+                .positional(Positional::new("args").variadic().help("Arguments passed to the program when running it"))
+                .flag(Flag::new("out").short(b'o').long("out").slash().value("FILE").default_value("a.out").help("Where to write the compiled program"))
+                .flag(Flag::new("level").short(b'O').value("LEVEL").default_value("0").env("BTF_OPT_LEVEL").help("Optimization level"))
+                .flag(Flag::new("color").long("color").value("WHEN").section("Display").help("When to color the output, either auto, always or never"))
+                .flag(Flag::new("help").short(b'h').long("help").help("Print this help"));
+        }
+
+        #[test]
+        fn help() {
+            let command = help_command();
+            let expected = concat!(
+                "Compiler for the back-to-front language\n",
+                "\n",
+                "Usage: btf [OPTIONS] <input> [args]...\n",
+                "\n",
+                "Arguments:\n",
+                "  <input>    Source file to compile\n",
+                "  [args]...  Arguments passed to the program when running it\n",
+                "\n",
+                "Options:\n",
+                "  -o, --out, /out <FILE>  Where to write the compiled program [default: a.out]\n",
+                "  -O <LEVEL>              Optimization level [default: 0] [env: BTF_OPT_LEVEL]\n",
+                "  -h, --help              Print this help\n",
+                "\n",
+                "Display:\n",
+                "      --color <WHEN>  When to color the output, either auto, always or never\n",
+            );
+            assert_eq!(command.help(HelpFormat::PLAIN).to_string(), expected);
+        }
+
+        #[test]
+        fn help_wrapping() {
+            let command = help_command();
+            let expected = concat!(
+                "Compiler for the back-to-front language\n",
+                "\n",
+                "Usage: btf [OPTIONS] <input> [args]...\n",
+                "\n",
+                "Arguments:\n",
+                "  <input>    Source file to compile\n",
+                "  [args]...  Arguments passed to the\n",
+                "             program when running it\n",
+                "\n",
+                "Options:\n",
+                "  -o, --out, /out <FILE>  Where to write the\n",
+                "                          compiled program\n",
+                "                          [default: a.out]\n",
+                "  -O <LEVEL>              Optimization level\n",
+                "                          [default: 0] [env:\n",
+                "                          BTF_OPT_LEVEL]\n",
+                "  -h, --help              Print this help\n",
+                "\n",
+                "Display:\n",
+                "      --color <WHEN>  When to color the\n",
+                "                      output, either auto,\n",
+                "                      always or never\n",
+            );
+            assert_eq!(command.help(HelpFormat::PLAIN.with_width(40)).to_string(), expected);
+
+            let narrow = command.help(HelpFormat::PLAIN.with_width(10)).to_string();
+            assert!(
+                narrow.contains("  -h, --help              Print this help\n"),
+                "descriptions should not be wrapped narrower than the minimum width"
+            );
+        }
+
+        #[test]
+        fn help_colored() {
+            let command = Command::new("btf").flag(Flag::new("help").short(b'h').help("Print this help"));
+            let heading = format!("{}{}", AnsiCode::Bold, AnsiFg::LightGreen);
+            let flag = format!("{}{}", AnsiCode::Bold, AnsiFg::LightCyan);
+            let reset = AnsiCode::Default;
+            let expected = format!(
+                "{heading}Usage:{reset} btf [OPTIONS]\n\n{heading}Options:{reset}\n  {flag}-h{reset}  Print this help\n"
+            );
+            assert_eq!(command.help(HelpFormat::COLORED).to_string(), expected);
+        }
+
+        fn driver() -> Command {
+            return Command::new("btf")
+                .about("Compiler for the back-to-front language")
+                .flag(Flag::new("color").long("color").value("WHEN").global().help("When to color the output"))
+                .flag(Flag::new("verbose").short(b'v').long("verbose").repeatable().global().help("Print more details"))
+                .subcommand(
+                    Command::new("build")
+                        .about("Compile a program")
+                        .flag(Flag::new("out").short(b'o').value("FILE").help("Where to write the program"))
+                        .positional(Positional::new("input").required().help("Source file")),
+                )
+                .subcommand(
+                    Command::new("run")
+                        .about("Compile and run a program")
+                        .positional(Positional::new("input").required())
+                        .positional(Positional::new("args").variadic()),
+                )
+                .subcommand(Command::new("check").about("Check a program for errors"))
+                .subcommand(
+                    Command::new("fmt")
+                        .about("Format source files")
+                        .subcommand(Command::new("check").about("Check the formatting without changing files")),
+                );
+        }
+
+        #[test]
+        fn parse_subcommands() {
+            let command = driver();
+
+            let args = ["-v", "build", "-vo", "a.out", "--color=never", "main.btf"];
+            let matches = command.parse(&args).unwrap();
+            assert_eq!(matches.count("verbose"), 2);
+            assert_eq!(matches.value("color"), Some(OsStr::new("never")));
+            let (name, build) = matches.subcommand().unwrap();
+            assert_eq!(name, "build");
+            assert_eq!(build.value("out"), Some(OsStr::new("a.out")));
+            assert_eq!(build.value("input"), Some(OsStr::new("main.btf")));
+            assert!(!build.is_present("verbose"), "global flags should be collected in the top-level matches");
+
+            let run_args = ["run", "main.btf", "build", "-v"];
+            let run_matches = command.parse(&run_args).unwrap();
+            let (_, run) = run_matches.subcommand().unwrap();
+            assert_eq!(run.values("args"), [OsStr::new("build")]);
+            assert_eq!(run_matches.count("verbose"), 1);
+
+            let fmt_args = ["fmt", "check"];
+            let fmt_matches = command.parse(&fmt_args).unwrap();
+            let (_, fmt) = fmt_matches.subcommand().unwrap();
+            assert_eq!(fmt.subcommand().map(|(nested, _)| return nested), Some("check"));
+
+            assert_eq!(command.parse(&["-v"]).unwrap().subcommand, None);
+        }
+
+        #[test]
+        fn subcommand_errors() {
+            let command = driver();
+
+            let error = command.parse(&["biuld", "main.btf"]).unwrap_err();
+            assert_eq!(
+                error,
+                ParseError::UnknownSubcommand { arg: OsStr::new("biuld"), suggestion: Some("build"), span: ArgSpan::new(0, 0, 5) }
+            );
+            assert_eq!(error.to_string(), "unknown subcommand `biuld`, did you mean `build`?");
+
+            assert_eq!(command.parse(&["--", "build"]).unwrap_err().to_string(), "unexpected argument `build`");
+            assert_eq!(command.parse(&["xyz"]).unwrap_err().to_string(), "unknown subcommand `xyz`");
+
+            assert_eq!(
+                command.parse(&["check", "-o", "a.out"]),
+                Err(ParseError::UnknownFlag { arg: OsStr::new("-o"), span: ArgSpan::new(1, 0, 2) })
+            );
+            assert_eq!(
+                command.parse(&["--color", "never", "build", "--color=always", "--color", "auto", "main.btf"]),
+                Err(ParseError::Repeated { id: "color", span: ArgSpan::new(3, 0, 7) })
+            );
+            assert_eq!(
+                command.parse(&["--color", "never", "build", "--color=always", "main.btf"]),
+                Err(ParseError::Repeated { id: "color", span: ArgSpan::new(3, 0, 7) })
+            );
+
+            assert_eq!(edit_distance("biuld", "build"), 2);
+            assert_eq!(edit_distance("", "fmt"), 3);
+            assert_eq!(edit_distance("check", "check"), 0);
+            assert_eq!(command.suggest_subcommand("chek"), Some("check"));
+            assert_eq!(command.suggest_subcommand("fnt"), Some("fmt"));
+            assert_eq!(command.suggest_subcommand("test"), None);
+        }
+
+        #[test]
+        fn subcommand_help() {
+            let command = driver();
+            let expected = concat!(
+                "Compiler for the back-to-front language\n",
+                "\n",
+                "Usage: btf [OPTIONS] <COMMAND>\n",
+                "\n",
+                "Commands:\n",
+                "  build  Compile a program\n",
+                "  run    Compile and run a program\n",
+                "  check  Check a program for errors\n",
+                "  fmt    Format source files\n",
+                "\n",
+                "Options:\n",
+                "      --color <WHEN>  When to color the output\n",
+                "  -v, --verbose       Print more details\n",
+            );
+            assert_eq!(command.help(HelpFormat::PLAIN).to_string(), expected);
+
+            let expected_build = concat!(
+                "Compile a program\n",
+                "\n",
+                "Usage: btf build [OPTIONS] <input>\n",
+                "\n",
+                "Arguments:\n",
+                "  <input>  Source file\n",
+                "\n",
+                "Options:\n",
+                "  -o <FILE>           Where to write the program\n",
+                "      --color <WHEN>  When to color the output\n",
+                "  -v, --verbose       Print more details\n",
+            );
+            assert_eq!(command.subcommand_help(&["build"], HelpFormat::PLAIN).unwrap().to_string(), expected_build);
+
+            let help = command.subcommand_help(&["fmt", "check"], HelpFormat::PLAIN).unwrap().to_string();
+            assert!(help.starts_with("Check the formatting without changing files\n\nUsage: btf fmt check [OPTIONS]\n"), "{help}");
+            assert!(command.subcommand_help(&["biuld"], HelpFormat::PLAIN).is_none(), "unknown subcommands should have no help");
+        }
+    }
+}
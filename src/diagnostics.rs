@@ -0,0 +1,1757 @@
+use crate::{
+    cli::{AnsiCode, AnsiFg},
+    src_code::{
+        ColumnKind, Expansions, ExpnKind, ExpnSpan, FileId, LineColumn, Span, SrcFile, SrcMap,
+        TextEdit, column_of, line_starts, lines,
+    },
+    uoffset8, uoffset32,
+};
+use alloc::collections::{BTreeMap, BTreeSet};
+use core::{
+    fmt::{Display, Write},
+    hash::{BuildHasher as _, BuildHasherDefault},
+};
+use std::{collections::HashMap, hash::DefaultHasher};
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+    Help,
+}
+
+impl Severity {
+    #[must_use]
+    #[inline]
+    pub const fn color(self) -> AnsiFg {
+        return match self {
+            Self::Error => AnsiFg::LightRed,
+            Self::Warning => AnsiFg::LightYellow,
+            Self::Note => AnsiFg::LightGreen,
+            Self::Help => AnsiFg::LightCyan,
+        };
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return match self {
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning"),
+            Self::Note => write!(f, "note"),
+            Self::Help => write!(f, "help"),
+        };
+    }
+}
+
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub struct Label {
+    pub span: Span,
+
+    /// Can be empty, in which case only the span is underlined
+    pub message: String,
+}
+
+impl Label {
+    #[must_use]
+    #[inline(always)]
+    pub const fn new(span: Span, message: String) -> Self {
+        return Self { span, message };
+    }
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Option<String>,
+    pub message: String,
+
+    /// Underlined with `^`
+    pub primary: Label,
+
+    /// Underlined with `-`
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+    pub helps: Vec<String>,
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl Diagnostic {
+    #[must_use]
+    #[inline]
+    pub const fn new(severity: Severity, message: String, span: Span) -> Self {
+        return Self {
+            severity,
+            code: None,
+            message,
+            primary: Label { span, message: String::new() },
+            secondary: Vec::new(),
+            notes: Vec::new(),
+            helps: Vec::new(),
+            suggestions: Vec::new(),
+        };
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn error(message: String, span: Span) -> Self {
+        return Self::new(Severity::Error, message, span);
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn warning(message: String, span: Span) -> Self {
+        return Self::new(Severity::Warning, message, span);
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn note(message: String, span: Span) -> Self {
+        return Self::new(Severity::Note, message, span);
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn help(message: String, span: Span) -> Self {
+        return Self::new(Severity::Help, message, span);
+    }
+
+    #[must_use]
+    pub fn with_code(mut self, code: String) -> Self {
+        self.code = Some(code);
+        return self;
+    }
+
+    #[must_use]
+    pub fn with_primary_message(mut self, message: String) -> Self {
+        self.primary.message = message;
+        return self;
+    }
+
+    #[must_use]
+    pub fn with_label(mut self, span: Span, message: String) -> Self {
+        self.secondary.push(Label { span, message });
+        return self;
+    }
+
+    #[must_use]
+    pub fn with_note(mut self, note: String) -> Self {
+        self.notes.push(note);
+        return self;
+    }
+
+    #[must_use]
+    pub fn with_help(mut self, help: String) -> Self {
+        self.helps.push(help);
+        return self;
+    }
+
+    #[must_use]
+    pub fn with_suggestion(mut self, suggestion: Suggestion) -> Self {
+        self.suggestions.push(suggestion);
+        return self;
+    }
+
+    /// Walks the expansions `span` comes from back to the code written by the user, labelling
+    /// each call site in the same file as `span` with "in this macro invocation", and noting the
+    /// location of the ones in other files
+    #[must_use]
+    pub fn with_expansion_backtrace(
+        mut self,
+        span: ExpnSpan,
+        expansions: &Expansions,
+        src_map: &SrcMap,
+    ) -> Self {
+        let mut originates_in = None;
+        for expansion in expansions.backtrace(span) {
+            let in_this = match &expansion.kind {
+                ExpnKind::Macro { name } => {
+                    if originates_in.is_none() {
+                        originates_in = Some(name.as_str());
+                    }
+                    "in this macro invocation".to_owned()
+                }
+                ExpnKind::Desugaring { .. } => format!("in this {}", expansion.kind),
+            };
+
+            let call_site = expansion.call_site.span;
+            if call_site.file == span.span.file {
+                self.secondary.push(Label { span: call_site.span, message: in_this });
+            } else if let Some(location) = src_map.locate(call_site) {
+                self.notes.push(format!("{in_this} at {location}"));
+            } else {
+                self.notes.push(in_this);
+            }
+        }
+
+        if let Some(name) = originates_in {
+            self.notes.push(format!("this {} originates in the macro `{name}`", self.severity));
+        }
+        return self;
+    }
+}
+
+/// How confident a [`Suggestion`] is of fixing the code
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Applicability {
+    /// The suggestion is definitely correct and can be applied automatically
+    MachineApplicable,
+
+    /// The suggestion may not be what the user intended
+    MaybeIncorrect,
+
+    /// The suggestion contains placeholders the user has to fill in
+    HasPlaceholders,
+
+    #[default]
+    Unspecified,
+}
+
+impl Applicability {
+    #[must_use]
+    #[inline]
+    pub const fn as_str(self) -> &'static str {
+        return match self {
+            Self::MachineApplicable => "MachineApplicable",
+            Self::MaybeIncorrect => "MaybeIncorrect",
+            Self::HasPlaceholders => "HasPlaceholders",
+            Self::Unspecified => "Unspecified",
+        };
+    }
+}
+
+/// Edits that fix the code a [`Diagnostic`] is about
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub struct Suggestion {
+    pub message: String,
+    pub edits: Vec<TextEdit>,
+    pub applicability: Applicability,
+}
+
+impl Suggestion {
+    #[must_use]
+    #[inline(always)]
+    pub const fn new(message: String, edits: Vec<TextEdit>, applicability: Applicability) -> Self {
+        return Self { message, edits, applicability };
+    }
+
+    #[must_use]
+    pub fn replace(
+        message: String,
+        span: Span,
+        replacement: String,
+        applicability: Applicability,
+    ) -> Self {
+        return Self { message, edits: vec![TextEdit::new(span, replacement)], applicability };
+    }
+
+    #[must_use]
+    pub fn insert(
+        message: String,
+        offset: uoffset32,
+        text: String,
+        applicability: Applicability,
+    ) -> Self {
+        return Self { message, edits: vec![TextEdit::insert(offset, text)], applicability };
+    }
+
+    #[must_use]
+    pub fn delete(message: String, span: Span, applicability: Applicability) -> Self {
+        return Self { message, edits: vec![TextEdit::delete(span)], applicability };
+    }
+}
+
+/// Collects the [`Suggestion`]s for the files of a [`SrcMap`] to apply all at once, rejecting the
+/// ones that would overlap
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub struct Fixer {
+    pub(crate) edits: BTreeMap<FileId, Vec<TextEdit>>,
+}
+
+impl Fixer {
+    #[must_use]
+    #[inline(always)]
+    pub const fn new() -> Self {
+        return Self { edits: BTreeMap::new() };
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn edits(&self, file: FileId) -> &[TextEdit] {
+        return self.edits.get(&file).map_or(&[], Vec::as_slice);
+    }
+
+    /// Files with edits to apply
+    #[inline]
+    pub fn files(&self) -> impl Iterator<Item = FileId> {
+        return self.edits.keys().copied();
+    }
+
+    /// Adds all the edits of `suggestion` to `file`, or none of them if any overlaps with another
+    /// edit
+    pub fn add(&mut self, file: FileId, suggestion: &Suggestion) -> Result<(), FixError> {
+        let _ = sorted_edits(self.edits(file).iter().chain(&suggestion.edits))?;
+        self.edits.entry(file).or_default().extend_from_slice(&suggestion.edits);
+        return Ok(());
+    }
+
+    /// Adds the [`Applicability::MachineApplicable`] suggestions of `reports`, returning why the
+    /// rejected ones could not be added
+    pub fn add_machine_applicable<'report, I: IntoIterator<Item = &'report Report>>(
+        &mut self,
+        reports: I,
+    ) -> Vec<(FileId, FixError)> {
+        let mut rejected = Vec::new();
+        for report in reports {
+            for suggestion in &report.diagnostic.suggestions {
+                if suggestion.applicability != Applicability::MachineApplicable {
+                    continue;
+                }
+                if let Err(err) = self.add(report.file, suggestion) {
+                    rejected.push((report.file, err));
+                }
+            }
+        }
+        return rejected;
+    }
+
+    /// Returns the fixed `code` of `file`
+    pub fn apply(&self, file: FileId, code: &str) -> Result<String, FixError> {
+        let (fixed, _) = patch(code, self.edits(file))?;
+        return Ok(fixed);
+    }
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum FixError {
+    Overlapping { span: Span, other: Span },
+    OutOfBounds { span: Span },
+    NotCharBoundary { span: Span },
+}
+
+impl Display for FixError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return match self {
+            Self::Overlapping { span, other } => write!(
+                f,
+                "edit at {}..{} overlaps with edit at {}..{}",
+                span.start(),
+                span.end(),
+                other.start(),
+                other.end()
+            ),
+            Self::OutOfBounds { span } => {
+                write!(f, "edit at {}..{} is out of bounds", span.start(), span.end())
+            }
+            Self::NotCharBoundary { span } => {
+                write!(f, "edit at {}..{} is not on character boundaries", span.start(), span.end())
+            }
+        };
+    }
+}
+
+#[expect(clippy::missing_trait_methods, reason = "the default implementations are fine")]
+impl core::error::Error for FixError {}
+
+/// Sorts `edits` by position, failing if any overlap or if insertions happen at the same offset,
+/// since the order they should be applied in would be ambiguous
+fn sorted_edits<'edit, I: IntoIterator<Item = &'edit TextEdit>>(
+    edits: I,
+) -> Result<Vec<&'edit TextEdit>, FixError> {
+    let mut sorted: Vec<&TextEdit> = edits.into_iter().collect();
+    sorted.sort_by_key(|edit| return (edit.span.start(), edit.span.end()));
+
+    let mut previous: Option<Span> = None;
+    let mut furthest: Option<Span> = None;
+    for edit in &sorted {
+        if let Some(other) = furthest
+            && edit.span.start() < other.end()
+        {
+            return Err(FixError::Overlapping { span: edit.span, other });
+        }
+        if let Some(other) = previous
+            && edit.span.start() == other.start()
+            && (edit.span.is_empty() || other.is_empty())
+        {
+            return Err(FixError::Overlapping { span: edit.span, other });
+        }
+
+        previous = Some(edit.span);
+        if furthest.is_none_or(|other| return edit.span.end() > other.end()) {
+            furthest = Some(edit.span);
+        }
+    }
+    return Ok(sorted);
+}
+
+/// Edits paired with the [`Span`] of their replacement in the edited code
+type Replacements<'edit> = Vec<(&'edit TextEdit, Span)>;
+
+/// Applies `edits` to `code`, returning the edited code and where the replacement of each edit
+/// ended up, in the order they appear in the edited code
+fn patch<'edit>(
+    code: &str,
+    edits: &'edit [TextEdit],
+) -> Result<(String, Replacements<'edit>), FixError> {
+    let sorted = sorted_edits(edits)?;
+    let mut patched = String::with_capacity(code.len());
+    let mut replacements = Vec::with_capacity(sorted.len());
+    let mut copied = 0;
+    for edit in sorted {
+        let start = edit.span.start() as usize;
+        let end = edit.span.end() as usize;
+        if end > code.len() {
+            return Err(FixError::OutOfBounds { span: edit.span });
+        }
+        if !code.is_char_boundary(start) || !code.is_char_boundary(end) {
+            return Err(FixError::NotCharBoundary { span: edit.span });
+        }
+
+        patched.push_str(&code[copied..start]);
+        let replacement_start = patched.len() as uoffset32;
+        patched.push_str(&edit.replacement);
+        let replacement_end = patched.len() as uoffset32;
+        replacements.push((edit, Span { start: replacement_start, end: replacement_end }));
+        copied = end;
+    }
+    patched.push_str(&code[copied..]);
+    return Ok((patched, replacements));
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+enum Style {
+    Plain,
+    Bold,
+    Gutter,
+    Label(AnsiFg),
+}
+
+impl Style {
+    const SECONDARY: Self = Self::Label(AnsiFg::LightBlue);
+}
+
+/// Lines of a multi-line span exceeding this count get elided
+const MAX_MULTILINE_LINES: uoffset32 = 4;
+
+#[derive(Clone, Copy, Debug)]
+struct Annotation<'diagnostic> {
+    start_line: uoffset32,
+    start_column: uoffset32,
+    end_line: uoffset32,
+
+    /// Non inclusive
+    end_column: uoffset32,
+    message: &'diagnostic str,
+    marker: char,
+    style: Style,
+}
+
+impl Annotation<'_> {
+    #[inline(always)]
+    const fn is_multiline(&self) -> bool {
+        return self.start_line != self.end_line;
+    }
+}
+
+/// A row of annotations drawn below a source line, indexed by display column
+#[derive(Clone, Debug, Default)]
+struct Row {
+    cells: Vec<(char, Style)>,
+}
+
+impl Row {
+    fn put(&mut self, index: usize, character: char, style: Style) {
+        if index >= self.cells.len() {
+            self.cells.resize(index + 1, (' ', Style::Plain));
+        }
+        self.cells[index] = (character, style);
+    }
+}
+
+/// Writes `text` followed by a newline, expanding tabs to spaces
+fn write_line_text<W: Write>(out: &mut W, kind: ColumnKind, text: &str) -> core::fmt::Result {
+    let mut column = 0;
+    for character in text.chars() {
+        let next_column = kind.advance(column, character);
+        if character == '\t' {
+            for _ in column..next_column {
+                out.write_char(' ')?;
+            }
+        } else {
+            out.write_char(character)?;
+        }
+        column = next_column;
+    }
+    return writeln!(out);
+}
+
+/// Renders diagnostics the way modern compilers do:
+///
+/// ```text
+/// error[E0001]: mismatched types
+///  --> main.btf:1:9
+///   |
+/// 1 | let x = "a" + 1;
+///   |         ^^^ expected integer
+///   |               - found here
+///   |
+///   = note: strings cannot be added to integers
+/// ```
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct Renderer {
+    pub colored: bool,
+    pub tab_width: uoffset8,
+}
+
+impl Default for Renderer {
+    #[inline(always)]
+    fn default() -> Self {
+        return Self::PLAIN;
+    }
+}
+
+impl Renderer {
+    pub const PLAIN: Self = Self { colored: false, tab_width: ColumnKind::DEFAULT_TAB_WIDTH };
+    pub const COLORED: Self = Self { colored: true, tab_width: ColumnKind::DEFAULT_TAB_WIDTH };
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn display<'diagnostic>(
+        self,
+        src: &'diagnostic SrcFile,
+        diagnostic: &'diagnostic Diagnostic,
+    ) -> RenderedDiagnostic<'diagnostic> {
+        return RenderedDiagnostic { renderer: self, src, diagnostic };
+    }
+
+    fn write_styled<W: Write>(self, out: &mut W, style: Style, text: &str) -> core::fmt::Result {
+        if !self.colored {
+            return out.write_str(text);
+        }
+
+        return match style {
+            Style::Plain => out.write_str(text),
+            Style::Bold => write!(out, "{}{text}{}", AnsiCode::Bold, AnsiCode::Default),
+            Style::Gutter => {
+                write!(out, "{}{}{text}{}", AnsiCode::Bold, AnsiFg::LightBlue, AnsiCode::Default)
+            }
+            Style::Label(color) => {
+                write!(out, "{}{color}{text}{}", AnsiCode::Bold, AnsiCode::Default)
+            }
+        };
+    }
+
+    fn write_row<W: Write>(self, out: &mut W, row: &Row) -> core::fmt::Result {
+        let mut cells = row.cells.as_slice();
+        while let [rest @ .., (' ', _)] = cells {
+            cells = rest;
+        }
+
+        let mut text = String::new();
+        let mut current_style = Style::Plain;
+        for &(character, style) in cells {
+            if style != current_style && character != ' ' {
+                self.write_styled(out, current_style, &text)?;
+                text.clear();
+                current_style = style;
+            }
+            text.push(character);
+        }
+        self.write_styled(out, current_style, &text)?;
+        return writeln!(out);
+    }
+
+    fn annotation<'diagnostic>(
+        src: &SrcFile,
+        kind: ColumnKind,
+        label: &'diagnostic Label,
+        marker: char,
+        style: Style,
+    ) -> Option<Annotation<'diagnostic>> {
+        let start = src.line_column_of(label.span.start(), kind)?;
+        let end = src.line_column_of(label.span.end(), kind)?;
+        let start_line = start.line - 1;
+        let start_column = start.column - 1;
+        let mut end_line = end.line - 1;
+        let mut end_column = end.column - 1;
+
+        // spans ending right after a line terminator are shown as ending on the previous line
+        if end_line > start_line && end_column == 0 {
+            end_line -= 1;
+            let line = src.line_text(end_line)?;
+            end_column = column_of(line, line.len() as uoffset32, kind)? + 1;
+        }
+
+        if end_line == start_line {
+            end_column = end_column.max(start_column + 1);
+        } else {
+            end_column = end_column.max(1);
+        }
+
+        return Some(Annotation {
+            start_line,
+            start_column,
+            end_line,
+            end_column,
+            message: &label.message,
+            marker,
+            style,
+        });
+    }
+
+    pub fn render<W: Write>(
+        self,
+        out: &mut W,
+        src: &SrcFile,
+        diagnostic: &Diagnostic,
+    ) -> core::fmt::Result {
+        let kind = ColumnKind::Display { tab_width: self.tab_width };
+        let severity_style = Style::Label(diagnostic.severity.color());
+
+        let mut severity = diagnostic.severity.to_string();
+        if let Some(code) = &diagnostic.code {
+            write!(severity, "[{code}]")?;
+        }
+        self.write_styled(out, severity_style, &severity)?;
+        self.write_styled(out, Style::Bold, ": ")?;
+        self.write_styled(out, Style::Bold, &diagnostic.message)?;
+        writeln!(out)?;
+
+        let mut annotations = Vec::with_capacity(diagnostic.secondary.len() + 1);
+        let primary = Self::annotation(src, kind, &diagnostic.primary, '^', severity_style);
+        if let Some(annotation) = primary {
+            annotations.push(annotation);
+        }
+        for label in &diagnostic.secondary {
+            if let Some(annotation) = Self::annotation(src, kind, label, '-', Style::SECONDARY) {
+                annotations.push(annotation);
+            }
+        }
+
+        let mut lines = BTreeSet::new();
+        for annotation in &annotations {
+            let _ = lines.insert(annotation.start_line);
+            let _ = lines.insert(annotation.end_line);
+            if annotation.end_line - annotation.start_line <= MAX_MULTILINE_LINES {
+                lines.extend(annotation.start_line..annotation.end_line);
+            } else {
+                let _ = lines.insert(annotation.start_line + 1);
+                let _ = lines.insert(annotation.end_line - 1);
+            }
+        }
+
+        let max_line_number = match lines.last() {
+            Some(&last_line) => last_line + 1,
+            None => 1,
+        };
+        let line_number_width = max_line_number.ilog10() as usize + 1;
+        let blank = " ".repeat(line_number_width);
+
+        if let Some(primary_annotation) = primary {
+            let char_location =
+                src.line_column_of(diagnostic.primary.span.start(), ColumnKind::Char);
+            let column = match char_location {
+                Some(location) => location.column,
+                None => primary_annotation.start_column + 1,
+            };
+            self.write_styled(out, Style::Gutter, &format!("{blank}--> "))?;
+            writeln!(out, "{}:{}:{column}", src.name(), primary_annotation.start_line + 1)?;
+        }
+
+        let gutter_separator = format!("{blank} |");
+        if !lines.is_empty() {
+            self.write_styled(out, Style::Gutter, &gutter_separator)?;
+            writeln!(out)?;
+        }
+
+        let multiline: Vec<&Annotation<'_>> =
+            annotations.iter().filter(|annotation| return annotation.is_multiline()).collect();
+        let gutter_width = if multiline.is_empty() { 0 } else { multiline.len() + 1 };
+        let mut active = vec![false; multiline.len()];
+
+        let mut single_line: Vec<&Annotation<'_>> =
+            annotations.iter().filter(|annotation| return !annotation.is_multiline()).collect();
+        single_line
+            .sort_by_key(|annotation| return (annotation.start_column, annotation.end_column));
+
+        let mut previous_line = None;
+        for &line in &lines {
+            if let Some(previous) = previous_line
+                && line > previous + 1
+            {
+                self.write_styled(out, Style::Gutter, "...")?;
+                writeln!(out)?;
+            }
+            previous_line = Some(line);
+
+            let Some(text) = src.line_text(line) else {
+                continue;
+            };
+
+            self.write_styled(out, Style::Gutter, &format!("{:>line_number_width$} | ", line + 1))?;
+            for (slot, annotation) in multiline.iter().enumerate() {
+                if active[slot] {
+                    self.write_styled(out, annotation.style, "|")?;
+                } else {
+                    out.write_char(' ')?;
+                }
+            }
+            if gutter_width > 0 {
+                out.write_char(' ')?;
+            }
+            write_line_text(out, kind, text)?;
+
+            let row_with_gutter = |active_slots: &[bool]| -> Row {
+                let mut row = Row::default();
+                for (slot, annotation) in multiline.iter().enumerate() {
+                    if active_slots[slot] {
+                        row.put(slot, '|', annotation.style);
+                    }
+                }
+                return row;
+            };
+
+            for (slot, annotation) in multiline.iter().enumerate() {
+                if annotation.start_line != line {
+                    continue;
+                }
+                let mut row = row_with_gutter(&active);
+                let marker_index = gutter_width + annotation.start_column as usize;
+                for index in slot + 1..marker_index {
+                    row.put(index, '_', annotation.style);
+                }
+                row.put(marker_index, annotation.marker, annotation.style);
+                self.write_styled(out, Style::Gutter, &gutter_separator)?;
+                out.write_char(' ')?;
+                self.write_row(out, &row)?;
+                active[slot] = true;
+            }
+
+            for annotation in &single_line {
+                if annotation.start_line != line {
+                    continue;
+                }
+                let mut row = row_with_gutter(&active);
+                let start_index = gutter_width + annotation.start_column as usize;
+                let end_index = gutter_width + annotation.end_column as usize;
+                for index in start_index..end_index {
+                    row.put(index, annotation.marker, annotation.style);
+                }
+                if !annotation.message.is_empty() {
+                    for (index, character) in annotation.message.chars().enumerate() {
+                        row.put(end_index + 1 + index, character, annotation.style);
+                    }
+                }
+                self.write_styled(out, Style::Gutter, &gutter_separator)?;
+                out.write_char(' ')?;
+                self.write_row(out, &row)?;
+            }
+
+            for (slot, annotation) in multiline.iter().enumerate() {
+                if annotation.end_line != line {
+                    continue;
+                }
+                let mut row = row_with_gutter(&active);
+                let marker_index = gutter_width + annotation.end_column as usize - 1;
+                for index in slot + 1..marker_index {
+                    row.put(index, '_', annotation.style);
+                }
+                row.put(marker_index, annotation.marker, annotation.style);
+                if !annotation.message.is_empty() {
+                    for (index, character) in annotation.message.chars().enumerate() {
+                        row.put(marker_index + 2 + index, character, annotation.style);
+                    }
+                }
+                self.write_styled(out, Style::Gutter, &gutter_separator)?;
+                out.write_char(' ')?;
+                self.write_row(out, &row)?;
+                active[slot] = false;
+            }
+        }
+
+        if !diagnostic.notes.is_empty() || !diagnostic.helps.is_empty() {
+            self.write_styled(out, Style::Gutter, &gutter_separator)?;
+            writeln!(out)?;
+        }
+        for (kind_name, messages) in [("note", &diagnostic.notes), ("help", &diagnostic.helps)] {
+            for message in messages {
+                self.write_styled(out, Style::Gutter, &format!("{blank} = "))?;
+                self.write_styled(out, Style::Bold, kind_name)?;
+                writeln!(out, ": {message}")?;
+            }
+        }
+
+        for suggestion in &diagnostic.suggestions {
+            self.render_suggestion(out, src, suggestion)?;
+        }
+
+        return Ok(());
+    }
+
+    /// Shows the code fixed by `suggestion`, marking inserted text with `+` and replaced text with
+    /// `~`, or the deleted text with `-` if the suggestion only deletes text
+    ///
+    /// Nothing is shown if the edits of `suggestion` cannot be applied to `src`
+    pub fn render_suggestion<W: Write>(
+        self,
+        out: &mut W,
+        src: &SrcFile,
+        suggestion: &Suggestion,
+    ) -> core::fmt::Result {
+        let Ok((patched, replacements)) = patch(src.code(), &suggestion.edits) else {
+            return Ok(());
+        };
+
+        let kind = ColumnKind::Display { tab_width: self.tab_width };
+        let style = Style::Label(Severity::Help.color());
+        self.write_styled(out, style, &Severity::Help.to_string())?;
+        self.write_styled(out, Style::Bold, ": ")?;
+        self.write_styled(out, Style::Bold, &suggestion.message)?;
+        writeln!(out)?;
+
+        let only_deletions = suggestion.edits.iter().all(|edit| return edit.replacement.is_empty());
+        let (text, markers): (&str, Vec<(Span, char)>) = if only_deletions {
+            let deleted = replacements.iter().map(|&(edit, _)| return (edit.span, '-')).collect();
+            (src.code(), deleted)
+        } else {
+            let replaced = replacements
+                .iter()
+                .map(|&(edit, replacement)| {
+                    let marker = if edit.span.is_empty() { '+' } else { '~' };
+                    return (replacement, marker);
+                })
+                .collect();
+            (&patched, replaced)
+        };
+
+        let (Some(&(first, _)), Some(&(last, _))) = (markers.first(), markers.last()) else {
+            return Ok(());
+        };
+        let text_line_starts = line_starts(text);
+        let first_line =
+            text_line_starts.partition_point(|&start| return start <= first.start()) - 1;
+        let last_line = text_line_starts.partition_point(|&start| return start <= last.end()) - 1;
+        let line_number_width = (last_line + 1).ilog10() as usize + 1;
+        let gutter_separator = format!("{} |", " ".repeat(line_number_width));
+
+        self.write_styled(out, Style::Gutter, &gutter_separator)?;
+        writeln!(out)?;
+        for (index, (line, _)) in lines(text).enumerate().take(last_line + 1).skip(first_line) {
+            let Some(line_text) = line.slice(text) else {
+                continue;
+            };
+            self.write_styled(
+                out,
+                Style::Gutter,
+                &format!("{:>line_number_width$} | ", index + 1),
+            )?;
+            write_line_text(out, kind, line_text)?;
+
+            let mut row = Row::default();
+            for &(span, marker) in &markers {
+                let Some(marked) = span.intersect(line) else {
+                    continue;
+                };
+                let start_byte = marked.start() - line.start();
+                let end_byte = marked.end() - line.start();
+                let (Some(start_column), Some(end_column)) =
+                    (column_of(line_text, start_byte, kind), column_of(line_text, end_byte, kind))
+                else {
+                    continue;
+                };
+                for column in start_column..end_column {
+                    row.put(column as usize, marker, style);
+                }
+            }
+            if row.cells.is_empty() {
+                continue;
+            }
+            self.write_styled(out, Style::Gutter, &gutter_separator)?;
+            out.write_char(' ')?;
+            self.write_row(out, &row)?;
+        }
+        return Ok(());
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RenderedDiagnostic<'diagnostic> {
+    renderer: Renderer,
+    src: &'diagnostic SrcFile,
+    diagnostic: &'diagnostic Diagnostic,
+}
+
+impl Display for RenderedDiagnostic<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return self.renderer.render(f, self.src, self.diagnostic);
+    }
+}
+
+/// [`Diagnostic`] reported in a file
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct Report {
+    pub file: FileId,
+    pub diagnostic: Diagnostic,
+}
+
+/// Collection of reported [`Diagnostic`]s, ignoring duplicate reports
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Diagnostics {
+    pub(crate) reports: Vec<Report>,
+    /// Indices of the reports with the same hash
+    pub(crate) reported: HashMap<u64, Vec<uoffset32>, BuildHasherDefault<DefaultHasher>>,
+    pub(crate) error_limit: Option<uoffset32>,
+    pub(crate) errors: uoffset32,
+    pub(crate) warnings: uoffset32,
+}
+
+/// The compilation should stop because too many errors were reported
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct ErrorLimitReached {
+    pub limit: uoffset32,
+}
+
+impl Display for ErrorLimitReached {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return write!(f, "aborting after reaching the limit of {} errors", self.limit);
+    }
+}
+
+#[expect(clippy::missing_trait_methods, reason = "the default implementations are fine")]
+impl core::error::Error for ErrorLimitReached {}
+
+impl Diagnostics {
+    #[must_use]
+    #[inline(always)]
+    pub const fn new() -> Self {
+        return Self {
+            reports: Vec::new(),
+            reported: HashMap::with_hasher(BuildHasherDefault::new()),
+            error_limit: None,
+            errors: 0,
+            warnings: 0,
+        };
+    }
+
+    /// Stop accepting errors once `limit` errors have been reported
+    #[must_use]
+    #[inline(always)]
+    pub const fn with_error_limit(mut self, limit: uoffset32) -> Self {
+        self.error_limit = Some(limit);
+        return self;
+    }
+
+    /// Fails for the error reaching the error limit and for the errors after it, which are ignored,
+    /// while reports of any other severity are always collected
+    pub fn push(&mut self, file: FileId, diagnostic: Diagnostic) -> Result<(), ErrorLimitReached> {
+        let is_error = diagnostic.severity == Severity::Error;
+        if is_error
+            && let Some(limit) = self.error_limit
+            && self.errors >= limit
+        {
+            return Err(ErrorLimitReached { limit });
+        }
+
+        let report = Report { file, diagnostic };
+        let hash = self.reported.hasher().hash_one(&report);
+        let same_hash = self.reported.entry(hash).or_default();
+        if same_hash.iter().any(|&index| return self.reports[index as usize] == report) {
+            return Ok(());
+        }
+        same_hash.push(self.reports.len() as uoffset32);
+
+        match report.diagnostic.severity {
+            Severity::Error => self.errors += 1,
+            Severity::Warning => self.warnings += 1,
+            Severity::Note | Severity::Help => {}
+        }
+        self.reports.push(report);
+
+        if is_error
+            && let Some(limit) = self.error_limit
+            && self.errors >= limit
+        {
+            return Err(ErrorLimitReached { limit });
+        }
+        return Ok(());
+    }
+
+    /// Sorts the reports by file and primary [`Span`], keeping the order of the reports at the
+    /// same position
+    pub fn sort(&mut self) {
+        self.reports.sort_by_key(|report| return (report.file, report.diagnostic.primary.span));
+        self.reported.clear();
+        for (index, report) in self.reports.iter().enumerate() {
+            let hash = self.reported.hasher().hash_one(report);
+            self.reported.entry(hash).or_default().push(index as uoffset32);
+        }
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn reports(&self) -> &[Report] {
+        return self.reports.as_slice();
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn len(&self) -> usize {
+        return self.reports.len();
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn is_empty(&self) -> bool {
+        return self.reports.is_empty();
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn error_count(&self) -> uoffset32 {
+        return self.errors;
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn warning_count(&self) -> uoffset32 {
+        return self.warnings;
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn has_errors(&self) -> bool {
+        return self.errors > 0;
+    }
+
+    /// Renders every report, skipping the ones reported in files not in `src_map`
+    pub fn render<W: Write>(
+        &self,
+        renderer: Renderer,
+        out: &mut W,
+        src_map: &SrcMap,
+    ) -> core::fmt::Result {
+        for report in &self.reports {
+            let Some(src) = src_map.file(report.file) else {
+                continue;
+            };
+            renderer.render(out, src, &report.diagnostic)?;
+            writeln!(out)?;
+        }
+        return Ok(());
+    }
+
+    /// Emits every report, skipping the ones reported in files not in `src_map`
+    #[inline(always)]
+    pub fn emit<W: Write>(
+        &self,
+        emitter: Emitter<'_>,
+        out: &mut W,
+        src_map: &SrcMap,
+    ) -> core::fmt::Result {
+        return emitter.emit(out, src_map, &self.reports);
+    }
+
+    /// "N errors, M warnings" summary
+    #[must_use]
+    #[inline(always)]
+    pub const fn summary(&self, colored: bool) -> Summary {
+        return Summary { errors: self.errors, warnings: self.warnings, colored };
+    }
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct Summary {
+    pub errors: uoffset32,
+    pub warnings: uoffset32,
+    pub colored: bool,
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let counts = [(self.errors, Severity::Error), (self.warnings, Severity::Warning)];
+        for (index, (count, severity)) in counts.into_iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            let plural = if count == 1 { "" } else { "s" };
+            if self.colored && count > 0 {
+                write!(f, "{}{}", AnsiCode::Bold, severity.color())?;
+                write!(f, "{count} {severity}{plural}{}", AnsiCode::Default)?;
+            } else {
+                write!(f, "{count} {severity}{plural}")?;
+            }
+        }
+        return Ok(());
+    }
+}
+
+/// Machine-readable formats diagnostics can be emitted in
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum EmitFormat {
+    /// `file:line:column: severity: message`, one line per diagnostic, label, note and help
+    #[default]
+    Gnu,
+
+    /// One rustc-like JSON object per diagnostic per line
+    JsonLines,
+
+    /// A single SARIF 2.1.0 log
+    Sarif,
+}
+
+/// Serializes diagnostics to an [`EmitFormat`], with lines and columns counted in characters
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct Emitter<'tool> {
+    pub format: EmitFormat,
+
+    /// Name and version of the tool that produced the diagnostics, only used in SARIF logs
+    pub tool_name: &'tool str,
+    pub tool_version: Option<&'tool str>,
+}
+
+impl<'tool> Emitter<'tool> {
+    pub const SARIF_VERSION: &'static str = "2.1.0";
+    pub const SARIF_SCHEMA: &'static str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn new(format: EmitFormat, tool_name: &'tool str) -> Self {
+        return Self { format, tool_name, tool_version: None };
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn with_tool_version(mut self, tool_version: &'tool str) -> Self {
+        self.tool_version = Some(tool_version);
+        return self;
+    }
+
+    /// Emits every report, skipping the ones reported in files not in `src_map`
+    pub fn emit<W: Write>(
+        &self,
+        out: &mut W,
+        src_map: &SrcMap,
+        reports: &[Report],
+    ) -> core::fmt::Result {
+        return match self.format {
+            EmitFormat::Gnu => {
+                for report in reports {
+                    let Some(src) = src_map.file(report.file) else {
+                        continue;
+                    };
+                    Self::emit_gnu(out, src, &report.diagnostic)?;
+                }
+                Ok(())
+            }
+            EmitFormat::JsonLines => {
+                for report in reports {
+                    let Some(src) = src_map.file(report.file) else {
+                        continue;
+                    };
+                    Self::emit_json(out, src, &report.diagnostic)?;
+                }
+                Ok(())
+            }
+            EmitFormat::Sarif => self.emit_sarif(out, src_map, reports),
+        };
+    }
+
+    pub fn emit_gnu<W: Write>(
+        out: &mut W,
+        src: &SrcFile,
+        diagnostic: &Diagnostic,
+    ) -> core::fmt::Result {
+        let name = src.name();
+        let primary = match locate(src, diagnostic.primary.span) {
+            Some((start, _)) => format!("{name}:{start}"),
+            None => name.to_owned(),
+        };
+
+        write!(out, "{primary}: {}: {}", diagnostic.severity, diagnostic.message)?;
+        if let Some(code) = &diagnostic.code {
+            write!(out, " [{code}]")?;
+        }
+        writeln!(out)?;
+
+        if !diagnostic.primary.message.is_empty() {
+            writeln!(out, "{primary}: {}: {}", Severity::Note, diagnostic.primary.message)?;
+        }
+        for label in &diagnostic.secondary {
+            if label.message.is_empty() {
+                continue;
+            }
+            match locate(src, label.span) {
+                Some((start, _)) => write!(out, "{name}:{start}")?,
+                None => write!(out, "{name}")?,
+            }
+            writeln!(out, ": {}: {}", Severity::Note, label.message)?;
+        }
+        for note in &diagnostic.notes {
+            writeln!(out, "{primary}: {}: {note}", Severity::Note)?;
+        }
+        for help in &diagnostic.helps {
+            writeln!(out, "{primary}: {}: {help}", Severity::Help)?;
+        }
+        return Ok(());
+    }
+
+    pub fn emit_json<W: Write>(
+        out: &mut W,
+        src: &SrcFile,
+        diagnostic: &Diagnostic,
+    ) -> core::fmt::Result {
+        out.write_str("{\"$message_type\":\"diagnostic\",\"message\":")?;
+        write_json_str(out, &diagnostic.message)?;
+        out.write_str(",\"code\":")?;
+        match &diagnostic.code {
+            Some(code) => {
+                out.write_str("{\"code\":")?;
+                write_json_str(out, code)?;
+                out.write_str(",\"explanation\":null}")?;
+            }
+            None => out.write_str("null")?,
+        }
+        write!(out, ",\"level\":\"{}\",\"spans\":[", diagnostic.severity)?;
+        let primary = &diagnostic.primary;
+        write_json_span(out, src, primary.span, &primary.message, true, None)?;
+        for label in &diagnostic.secondary {
+            out.write_char(',')?;
+            write_json_span(out, src, label.span, &label.message, false, None)?;
+        }
+        out.write_str("],\"children\":[")?;
+
+        let children = diagnostic
+            .notes
+            .iter()
+            .map(|note| return (Severity::Note, note))
+            .chain(diagnostic.helps.iter().map(|help| return (Severity::Help, help)));
+        for (index, (severity, message)) in children.enumerate() {
+            if index > 0 {
+                out.write_char(',')?;
+            }
+            out.write_str("{\"message\":")?;
+            write_json_str(out, message)?;
+            write!(
+                out,
+                ",\"code\":null,\"level\":\"{severity}\",\"spans\":[],\"children\":[],\"rendered\":null}}"
+            )?;
+        }
+        for (index, suggestion) in diagnostic.suggestions.iter().enumerate() {
+            if index > 0 || !diagnostic.notes.is_empty() || !diagnostic.helps.is_empty() {
+                out.write_char(',')?;
+            }
+            out.write_str("{\"message\":")?;
+            write_json_str(out, &suggestion.message)?;
+            write!(out, ",\"code\":null,\"level\":\"{}\",\"spans\":[", Severity::Help)?;
+            for (edit_index, edit) in suggestion.edits.iter().enumerate() {
+                if edit_index > 0 {
+                    out.write_char(',')?;
+                }
+                let replacement = Some((edit.replacement.as_str(), suggestion.applicability));
+                write_json_span(out, src, edit.span, "", true, replacement)?;
+            }
+            out.write_str("],\"children\":[],\"rendered\":null}")?;
+        }
+        out.write_str("],\"rendered\":")?;
+        write_json_str(out, &Renderer::PLAIN.display(src, diagnostic).to_string())?;
+        return out.write_str("}\n");
+    }
+
+    /// Emits a single run with the results of every file, skipping the reports in files not in
+    /// `src_map`
+    pub fn emit_sarif<W: Write>(
+        &self,
+        out: &mut W,
+        src_map: &SrcMap,
+        reports: &[Report],
+    ) -> core::fmt::Result {
+        write!(
+            out,
+            "{{\"$schema\":\"{}\",\"version\":\"{}\",",
+            Self::SARIF_SCHEMA,
+            Self::SARIF_VERSION
+        )?;
+        out.write_str("\"runs\":[{\"tool\":{\"driver\":{\"name\":")?;
+        write_json_str(out, self.tool_name)?;
+        if let Some(version) = self.tool_version {
+            out.write_str(",\"version\":")?;
+            write_json_str(out, version)?;
+        }
+        out.write_str("}},\"columnKind\":\"unicodeCodePoints\",\"results\":[")?;
+
+        let results = reports.iter().filter_map(|report| {
+            let src = src_map.file(report.file)?;
+            return Some((src, &report.diagnostic));
+        });
+        for (index, (src, diagnostic)) in results.enumerate() {
+            if index > 0 {
+                out.write_char(',')?;
+            }
+            out.write_char('{')?;
+            if let Some(code) = &diagnostic.code {
+                out.write_str("\"ruleId\":")?;
+                write_json_str(out, code)?;
+                out.write_char(',')?;
+            }
+            let level = match diagnostic.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+                Severity::Note | Severity::Help => "note",
+            };
+            write!(out, "\"level\":\"{level}\",\"message\":{{\"text\":")?;
+
+            // SARIF results have no notes or helps, so they are appended to the message
+            let mut text = diagnostic.message.clone();
+            for note in &diagnostic.notes {
+                write!(text, "\n{}: {note}", Severity::Note)?;
+            }
+            for help in &diagnostic.helps {
+                write!(text, "\n{}: {help}", Severity::Help)?;
+            }
+            write_json_str(out, &text)?;
+            out.write_str("},\"locations\":[")?;
+            write_sarif_location(out, src, &diagnostic.primary, None)?;
+            out.write_char(']')?;
+
+            if !diagnostic.secondary.is_empty() {
+                out.write_str(",\"relatedLocations\":[")?;
+                for (id, label) in diagnostic.secondary.iter().enumerate() {
+                    if id > 0 {
+                        out.write_char(',')?;
+                    }
+                    write_sarif_location(out, src, label, Some(id))?;
+                }
+                out.write_char(']')?;
+            }
+            out.write_char('}')?;
+        }
+        return out.write_str("]}]}\n");
+    }
+}
+
+/// 1-based line and character column of the start and end of `span`
+fn locate(src: &SrcFile, span: Span) -> Option<(LineColumn, LineColumn)> {
+    let start = src.line_column_of(span.start(), ColumnKind::Char)?;
+    let end = src.line_column_of(span.end(), ColumnKind::Char)?;
+    return Some((start, end));
+}
+
+fn write_json_str<W: Write>(out: &mut W, text: &str) -> core::fmt::Result {
+    out.write_char('"')?;
+    for character in text.chars() {
+        match character {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            '\r' => out.write_str("\\r")?,
+            '\t' => out.write_str("\\t")?,
+            '\u{0}'..='\u{1f}' | '\u{7f}' => write!(out, "\\u{:04x}", character as u32)?,
+            _ => out.write_char(character)?,
+        }
+    }
+    return out.write_char('"');
+}
+
+fn write_json_span<W: Write>(
+    out: &mut W,
+    src: &SrcFile,
+    span: Span,
+    label: &str,
+    is_primary: bool,
+    suggestion: Option<(&str, Applicability)>,
+) -> core::fmt::Result {
+    out.write_str("{\"file_name\":")?;
+    write_json_str(out, src.name())?;
+    write!(out, ",\"byte_start\":{},\"byte_end\":{}", span.start(), span.end())?;
+    match locate(src, span) {
+        Some((start, end)) => write!(
+            out,
+            ",\"line_start\":{},\"line_end\":{},\"column_start\":{},\"column_end\":{}",
+            start.line, end.line, start.column, end.column
+        )?,
+        None => out.write_str(
+            ",\"line_start\":null,\"line_end\":null,\"column_start\":null,\"column_end\":null",
+        )?,
+    }
+    write!(out, ",\"is_primary\":{is_primary},\"label\":")?;
+    if label.is_empty() {
+        out.write_str("null")?;
+    } else {
+        write_json_str(out, label)?;
+    }
+    match suggestion {
+        Some((replacement, applicability)) => {
+            out.write_str(",\"suggested_replacement\":")?;
+            write_json_str(out, replacement)?;
+            write!(out, ",\"suggestion_applicability\":\"{}\"", applicability.as_str())?;
+        }
+        None => {
+            out.write_str(",\"suggested_replacement\":null,\"suggestion_applicability\":null")?;
+        }
+    }
+    return out.write_char('}');
+}
+
+fn write_sarif_location<W: Write>(
+    out: &mut W,
+    src: &SrcFile,
+    label: &Label,
+    related_id: Option<usize>,
+) -> core::fmt::Result {
+    out.write_char('{')?;
+    if let Some(id) = related_id {
+        write!(out, "\"id\":{id},")?;
+    }
+
+    // URIs cannot contain some characters, which need to be percent-encoded
+    let mut uri = String::new();
+    for &byte in src.name().as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(byte as char);
+            }
+            b'\\' => uri.push('/'),
+            _ => write!(uri, "%{byte:02X}")?,
+        }
+    }
+    write!(out, "\"physicalLocation\":{{\"artifactLocation\":{{\"uri\":\"{uri}\"}},\"region\":{{")?;
+    if let Some((start, end)) = locate(src, label.span) {
+        write!(
+            out,
+            "\"startLine\":{},\"startColumn\":{},\"endLine\":{},\"endColumn\":{},",
+            start.line, start.column, end.line, end.column
+        )?;
+    }
+    write!(out, "\"byteOffset\":{},\"byteLength\":{}}}}}", label.span.start(), label.span.len())?;
+    if !label.message.is_empty() {
+        out.write_str(",\"message\":{\"text\":")?;
+        write_json_str(out, &label.message)?;
+        out.write_char('}')?;
+    }
+    return out.write_char('}');
+}
+
+#[expect(clippy::unwrap_used)]
+#[cfg(test)]
+#[rustfmt::skip]
+mod tests {
+    mod _0_1_2_functionality {
+        use crate::{
+            diagnostics::{
+                Applicability, Diagnostic, Diagnostics, EmitFormat, Emitter, ErrorLimitReached, FixError, Fixer, Renderer,
+                Report, Suggestion,
+            },
+            src_code::{ExpnKind, ExpnSpan, Expansions, FileId, FileSpan, Span, SrcFile, SrcMap},
+        };
+
+        fn src(code: &str) -> SrcFile {
+            return SrcFile::new("main.btf".to_owned(), code.to_owned()).unwrap();
+        }
+
+        #[test]
+        fn single_line() {
+            let src = src("let x = \"a\" + 1;\n");
+            let diagnostic = Diagnostic::error("mismatched types".to_owned(), Span::new(8, 11).unwrap())
+                .with_code("E0001".to_owned())
+                .with_primary_message("expected integer".to_owned())
+                .with_label(Span::new(14, 15).unwrap(), "found here".to_owned())
+                .with_note("strings cannot be added to integers".to_owned())
+                .with_help("try converting the integer".to_owned());
+
+            let expected = concat!(
+                "error[E0001]: mismatched types\n",
+                " --> main.btf:1:9\n",
+                "  |\n",
+                "1 | let x = \"a\" + 1;\n",
+                "  |         ^^^ expected integer\n",
+                "  |               - found here\n",
+                "  |\n",
+                "  = note: strings cannot be added to integers\n",
+                "  = help: try converting the integer\n",
+            );
+            assert_eq!(Renderer::PLAIN.display(&src, &diagnostic).to_string(), expected);
+        }
+
+        #[test]
+        fn multi_line() {
+            let src = src("fn main() {\n    let a = {\n        1\n    };\n}\n");
+            let diagnostic = Diagnostic::warning("unused block".to_owned(), Span::new(24, 41).unwrap())
+                .with_primary_message("this block".to_owned());
+
+            let expected = concat!(
+                "warning: unused block\n",
+                " --> main.btf:2:13\n",
+                "  |\n",
+                "2 |       let a = {\n",
+                "  |  _____________^\n",
+                "3 | |         1\n",
+                "4 | |     };\n",
+                "  | |_____^ this block\n",
+            );
+            assert_eq!(Renderer::PLAIN.display(&src, &diagnostic).to_string(), expected);
+        }
+
+        #[test]
+        fn elided_lines_and_tabs() {
+            let src = src("a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n\tk\n");
+            let diagnostic = Diagnostic::error("too long".to_owned(), Span::new(0, 13).unwrap())
+                .with_label(Span::new(21, 22).unwrap(), "tab".to_owned());
+
+            let expected = concat!(
+                "error: too long\n",
+                "  --> main.btf:1:1\n",
+                "   |\n",
+                " 1 |   a\n",
+                "   |  _^\n",
+                " 2 | | b\n",
+                "...\n",
+                " 6 | | f\n",
+                " 7 | | g\n",
+                "   | |_^\n",
+                "...\n",
+                "11 |       k\n",
+                "   |       - tab\n",
+            );
+            assert_eq!(Renderer::PLAIN.display(&src, &diagnostic).to_string(), expected);
+        }
+
+        #[test]
+        fn machine_readable() {
+            let mut src_map = SrcMap::new();
+            let main = src_map.add_code("src dir/main.btf".to_owned(), "let x = \"a\" + 1;\n".to_owned()).unwrap();
+            let lib = src_map.add_code("lib.btf".to_owned(), "fn f() {}\n".to_owned()).unwrap();
+            let diagnostic = Diagnostic::error("mismatched \"types\"".to_owned(), Span::new(8, 11).unwrap())
+                .with_code("E0001".to_owned())
+                .with_primary_message("expected integer".to_owned())
+                .with_label(Span::new(14, 15).unwrap(), "found here".to_owned())
+                .with_note("strings cannot be added to integers".to_owned());
+            let reports = [Report { file: main, diagnostic }];
+
+            let mut gnu = String::new();
+            Emitter::new(EmitFormat::Gnu, "btf").emit(&mut gnu, &src_map, &reports).unwrap();
+            let expected_gnu = concat!(
+                "src dir/main.btf:1:9: error: mismatched \"types\" [E0001]\n",
+                "src dir/main.btf:1:9: note: expected integer\n",
+                "src dir/main.btf:1:15: note: found here\n",
+                "src dir/main.btf:1:9: note: strings cannot be added to integers\n",
+            );
+            assert_eq!(gnu, expected_gnu);
+
+            let mut json = String::new();
+            Emitter::new(EmitFormat::JsonLines, "btf").emit(&mut json, &src_map, &reports).unwrap();
+            let expected_json = concat!(
+                r#"{"$message_type":"diagnostic","message":"mismatched \"types\"","code":{"code":"E0001","explanation":null},"level":"error","spans":["#,
+                r#"{"file_name":"src dir/main.btf","byte_start":8,"byte_end":11,"line_start":1,"line_end":1,"column_start":9,"column_end":12,"is_primary":true,"label":"expected integer","suggested_replacement":null,"suggestion_applicability":null},"#,
+                r#"{"file_name":"src dir/main.btf","byte_start":14,"byte_end":15,"line_start":1,"line_end":1,"column_start":15,"column_end":16,"is_primary":false,"label":"found here","suggested_replacement":null,"suggestion_applicability":null}"#,
+                r#"],"children":[{"message":"strings cannot be added to integers","code":null,"level":"note","spans":[],"children":[],"rendered":null}],"rendered":"error[E0001]: mismatched \"types\"\n"#,
+            );
+            assert!(json.starts_with(expected_json), "{json}");
+            assert!(json.ends_with("\"}\n"), "{json}");
+            assert_eq!(json.lines().count(), 1);
+
+            let mut sarif = String::new();
+            Emitter::new(EmitFormat::Sarif, "btf").with_tool_version("0.1.2").emit(&mut sarif, &src_map, &reports).unwrap();
+            let expected_sarif = concat!(
+                r#"{"$schema":"https://json.schemastore.org/sarif-2.1.0.json","version":"2.1.0","runs":[{"#,
+                r#""tool":{"driver":{"name":"btf","version":"0.1.2"}},"columnKind":"unicodeCodePoints","results":[{"#,
+                r#""ruleId":"E0001","level":"error","message":{"text":"mismatched \"types\"\nnote: strings cannot be added to integers"},"#,
+                r#""locations":[{"physicalLocation":{"artifactLocation":{"uri":"src%20dir/main.btf"},"#,
+                r#""region":{"startLine":1,"startColumn":9,"endLine":1,"endColumn":12,"byteOffset":8,"byteLength":3}},"message":{"text":"expected integer"}}],"#,
+                r#""relatedLocations":[{"id":0,"physicalLocation":{"artifactLocation":{"uri":"src%20dir/main.btf"},"#,
+                r#""region":{"startLine":1,"startColumn":15,"endLine":1,"endColumn":16,"byteOffset":14,"byteLength":1}},"message":{"text":"found here"}}]"#,
+                "}]}]}\n",
+            );
+            assert_eq!(sarif, expected_sarif);
+
+            let unused = Report { file: lib, diagnostic: Diagnostic::warning("unused function".to_owned(), Span::new(3, 4).unwrap()) };
+            let unknown_file = Report { file: FileId(2), diagnostic: Diagnostic::error("unknown".to_owned(), Span::new(0, 1).unwrap()) };
+            let reports_in_files = [reports[0].clone(), unused, unknown_file];
+            let mut gnu_in_files = String::new();
+            Emitter::new(EmitFormat::Gnu, "btf").emit(&mut gnu_in_files, &src_map, &reports_in_files).unwrap();
+            assert!(gnu_in_files.starts_with(expected_gnu), "{gnu_in_files}");
+            assert!(gnu_in_files.ends_with("\nlib.btf:1:4: warning: unused function\n"), "{gnu_in_files}");
+
+            let mut sarif_in_files = String::new();
+            Emitter::new(EmitFormat::Sarif, "btf").emit(&mut sarif_in_files, &src_map, &reports_in_files).unwrap();
+            assert_eq!(sarif_in_files.matches("\"results\":[").count(), 1, "{sarif_in_files}");
+            let expected_lib_result = concat!(
+                r#"},{"level":"warning","message":{"text":"unused function"},"#,
+                r#""locations":[{"physicalLocation":{"artifactLocation":{"uri":"lib.btf"},"#,
+                r#""region":{"startLine":1,"startColumn":4,"endLine":1,"endColumn":5,"byteOffset":3,"byteLength":1}}}]"#,
+                "}]}]}\n",
+            );
+            assert!(sarif_in_files.contains(r#"{"artifactLocation":{"uri":"src%20dir/main.btf"}"#), "{sarif_in_files}");
+            assert!(sarif_in_files.ends_with(expected_lib_result), "{sarif_in_files}");
+        }
+
+        #[test]
+        fn diagnostics_bag() {
+            let mut src_map = SrcMap::new();
+            let main = src_map.add_code("main.btf".to_owned(), "let x = 1;\nlet y = z;\n".to_owned()).unwrap();
+            let lib = src_map.add_code("lib.btf".to_owned(), "fn f() {}\n".to_owned()).unwrap();
+
+            let mut diagnostics = Diagnostics::new().with_error_limit(3);
+            let undefined = Diagnostic::error("undefined variable".to_owned(), Span::new(19, 20).unwrap()).with_code("E0002".to_owned());
+            diagnostics.push(main, undefined.clone()).unwrap();
+            diagnostics.push(main, undefined).unwrap();
+            diagnostics.push(lib, Diagnostic::warning("unused function".to_owned(), Span::new(3, 4).unwrap())).unwrap();
+            diagnostics.push(main, Diagnostic::warning("unused variable".to_owned(), Span::new(4, 5).unwrap())).unwrap();
+            diagnostics.push(main, Diagnostic::note("declared here".to_owned(), Span::new(0, 3).unwrap())).unwrap();
+            assert_eq!(diagnostics.len(), 4);
+            assert_eq!(diagnostics.error_count(), 1);
+            assert_eq!(diagnostics.warning_count(), 2);
+            assert_eq!(diagnostics.summary(false).to_string(), "1 error, 2 warnings");
+            assert_eq!(
+                diagnostics.summary(true).to_string(),
+                "\x1b[1m\x1b[91m1 error\x1b[0m, \x1b[1m\x1b[93m2 warnings\x1b[0m"
+            );
+
+            diagnostics.sort();
+            let order: Vec<&str> = diagnostics.reports().iter().map(|report| return report.diagnostic.message.as_str()).collect();
+            assert_eq!(order, ["declared here", "unused variable", "undefined variable", "unused function"]);
+
+            let mut rendered = String::new();
+            diagnostics.render(Renderer::PLAIN, &mut rendered, &src_map).unwrap();
+            assert!(rendered.starts_with("note: declared here\n --> main.btf:1:1\n"), "{rendered}");
+            assert!(rendered.contains("error[E0002]: undefined variable\n --> main.btf:2:9\n"), "{rendered}");
+
+            let mut emitted = String::new();
+            diagnostics.emit(Emitter::new(EmitFormat::Gnu, "btf"), &mut emitted, &src_map).unwrap();
+            let expected_emitted = concat!(
+                "main.btf:1:1: note: declared here\n",
+                "main.btf:1:5: warning: unused variable\n",
+                "main.btf:2:9: error: undefined variable [E0002]\n",
+                "lib.btf:1:4: warning: unused function\n",
+            );
+            assert_eq!(emitted, expected_emitted);
+
+            diagnostics.push(main, Diagnostic::error("first".to_owned(), Span::new(0, 1).unwrap())).unwrap();
+            let limit = diagnostics.push(main, Diagnostic::error("second".to_owned(), Span::new(1, 2).unwrap()));
+            assert_eq!(limit, Err(ErrorLimitReached { limit: 3 }));
+            let ignored = diagnostics.push(main, Diagnostic::error("third".to_owned(), Span::new(2, 3).unwrap()));
+            assert_eq!(ignored, Err(ErrorLimitReached { limit: 3 }));
+            diagnostics.push(main, Diagnostic::warning("after the limit".to_owned(), Span::new(1, 2).unwrap())).unwrap();
+            diagnostics.push(lib, Diagnostic::warning("unused function".to_owned(), Span::new(3, 4).unwrap())).unwrap();
+            assert_eq!(diagnostics.len(), 7);
+            assert_eq!(diagnostics.error_count(), 3);
+            assert_eq!(diagnostics.summary(false).to_string(), "3 errors, 3 warnings");
+            assert!(diagnostics.has_errors(), "errors were reported");
+
+            let mut no_errors = Diagnostics::new().with_error_limit(0);
+            no_errors.push(main, Diagnostic::warning("unused variable".to_owned(), Span::new(4, 5).unwrap())).unwrap();
+            no_errors.push(main, Diagnostic::help("rename it".to_owned(), Span::new(4, 5).unwrap())).unwrap();
+            let first_error = no_errors.push(main, Diagnostic::error("undefined variable".to_owned(), Span::new(19, 20).unwrap()));
+            assert_eq!(first_error, Err(ErrorLimitReached { limit: 0 }));
+            assert_eq!(no_errors.len(), 2);
+            assert!(!no_errors.has_errors(), "errors past the limit should be ignored");
+        }
+
+        #[test]
+        fn suggestions() {
+            let src = src("if a = b {\n    x\n}\n");
+            let comparison = Suggestion::replace("use `==` to compare".to_owned(), Span::new(5, 6).unwrap(), "==".to_owned(), Applicability::MachineApplicable);
+            let diagnostic = Diagnostic::error("expected comparison".to_owned(), Span::new(5, 6).unwrap())
+                .with_suggestion(comparison.clone());
+            let expected = concat!(
+                "error: expected comparison\n",
+                " --> main.btf:1:6\n",
+                "  |\n",
+                "1 | if a = b {\n",
+                "  |      ^\n",
+                "help: use `==` to compare\n",
+                "  |\n",
+                "1 | if a == b {\n",
+                "  |      ~~\n",
+            );
+            assert_eq!(Renderer::PLAIN.display(&src, &diagnostic).to_string(), expected);
+
+            let semicolon = Suggestion::insert("add a semicolon".to_owned(), 16, ";".to_owned(), Applicability::MachineApplicable);
+            let mut rendered = String::new();
+            Renderer::PLAIN.render_suggestion(&mut rendered, &src, &semicolon).unwrap();
+            assert_eq!(rendered, "help: add a semicolon\n  |\n2 |     x;\n  |      +\n");
+
+            let indentation = Suggestion::delete("remove the indentation".to_owned(), Span::new(11, 15).unwrap(), Applicability::MaybeIncorrect);
+            rendered.clear();
+            Renderer::PLAIN.render_suggestion(&mut rendered, &src, &indentation).unwrap();
+            assert_eq!(rendered, "help: remove the indentation\n  |\n2 |     x\n  | ----\n");
+
+            let main = FileId(0);
+            let lib = FileId(1);
+            let reports = [
+                Report { file: main, diagnostic: diagnostic.with_suggestion(indentation.clone()) },
+                Report { file: main, diagnostic: Diagnostic::error("expected `;`".to_owned(), Span::new(16, 16).unwrap()).with_suggestion(semicolon.clone()) },
+                Report { file: lib, diagnostic: Diagnostic::error("expected `;`".to_owned(), Span::new(16, 16).unwrap()).with_suggestion(semicolon) },
+            ];
+            let mut fixer = Fixer::new();
+            assert!(fixer.add_machine_applicable(&reports).is_empty(), "no suggestion should be rejected");
+            assert_eq!(fixer.files().collect::<Vec<_>>(), [main, lib]);
+            assert_eq!(fixer.edits(main).len(), 2);
+            assert_eq!(fixer.edits(lib).len(), 1);
+            assert_eq!(fixer.apply(main, src.code()), Ok("if a == b {\n    x;\n}\n".to_owned()));
+            assert_eq!(fixer.apply(lib, "if b = a {\n    y\n}\n"), Ok("if b = a {\n    y;\n}\n".to_owned()));
+            assert_eq!(fixer.apply(FileId(2), "unchanged"), Ok("unchanged".to_owned()));
+
+            let overlapping = Suggestion::replace("rename".to_owned(), Span::new(3, 6).unwrap(), "c ==".to_owned(), Applicability::MachineApplicable);
+            assert_eq!(fixer.add(main, &overlapping), Err(FixError::Overlapping { span: Span::new(5, 6).unwrap(), other: Span::new(3, 6).unwrap() }));
+            let same_insertion = Suggestion::insert("add a comma".to_owned(), 16, ",".to_owned(), Applicability::MachineApplicable);
+            assert_eq!(fixer.add(main, &same_insertion), Err(FixError::Overlapping { span: Span::new(16, 16).unwrap(), other: Span::new(16, 16).unwrap() }));
+            assert_eq!(fixer.edits(main).len(), 2);
+            assert_eq!(fixer.add(main, &comparison), Err(FixError::Overlapping { span: Span::new(5, 6).unwrap(), other: Span::new(5, 6).unwrap() }));
+            fixer.add(lib, &comparison).unwrap();
+
+            fixer.add(main, &indentation).unwrap();
+            assert_eq!(fixer.apply(main, src.code()), Ok("if a == b {\nx;\n}\n".to_owned()));
+
+            let out_of_bounds = Suggestion::insert("add a newline".to_owned(), 100, "\n".to_owned(), Applicability::MachineApplicable);
+            fixer.add(main, &out_of_bounds).unwrap();
+            assert_eq!(fixer.apply(main, src.code()), Err(FixError::OutOfBounds { span: Span::new(100, 100).unwrap() }));
+
+            rendered.clear();
+            Renderer::PLAIN.render_suggestion(&mut rendered, &src, &out_of_bounds).unwrap();
+            assert_eq!(rendered, "", "suggestions that cannot be applied should not be shown");
+        }
+
+        #[test]
+        fn expansion_backtrace() {
+            let mut src_map = SrcMap::new();
+            let main = src_map.add_code("main.btf".to_owned(), "fn main() {\n    let v = make!(1);\n}\n".to_owned()).unwrap();
+            let lib = src_map.add_code("lib.btf".to_owned(), "macro make(x) { [x, y] }\n".to_owned()).unwrap();
+
+            let mut expansions = Expansions::new();
+            let make = expansions.add(
+                ExpnKind::Macro { name: "make".to_owned() },
+                ExpnSpan::root(FileSpan::new(main, Span::new(24, 32).unwrap())),
+            );
+            let array = expansions.add(
+                ExpnKind::Desugaring { name: "array literal".to_owned() },
+                ExpnSpan::new(FileSpan::new(lib, Span::new(16, 22).unwrap()), make),
+            );
+
+            let span = ExpnSpan::new(FileSpan::new(lib, Span::new(20, 21).unwrap()), array);
+            let diagnostic = Diagnostic::error("undefined variable `y`".to_owned(), span.span.span)
+                .with_expansion_backtrace(span, &expansions, &src_map);
+            let expected = concat!(
+                "error: undefined variable `y`\n",
+                " --> lib.btf:1:21\n",
+                "  |\n",
+                "1 | macro make(x) { [x, y] }\n",
+                "  |                 ------ in this desugaring of array literal\n",
+                "  |                     ^\n",
+                "  |\n",
+                "  = note: in this macro invocation at main.btf:2:13\n",
+                "  = note: this error originates in the macro `make`\n",
+            );
+            assert_eq!(Renderer::PLAIN.display(src_map.file(lib).unwrap(), &diagnostic).to_string(), expected);
+        }
+
+        #[test]
+        fn colored() {
+            let src = src("x");
+            let diagnostic = Diagnostic::error("bad".to_owned(), Span::new(0, 1).unwrap());
+            let rendered = Renderer::COLORED.display(&src, &diagnostic).to_string();
+            assert!(rendered.starts_with("\x1b[1m\x1b[91merror\x1b[0m"));
+            assert!(rendered.contains("\x1b[1m\x1b[91m^\x1b[0m"));
+        }
+    }
+}
@@ -1,5 +1,9 @@
+extern crate alloc;
+
 pub mod cli;
+pub mod diagnostics;
 pub mod digit;
+pub mod lsp;
 pub mod src_code;
 pub mod x86_64;
 
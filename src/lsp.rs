@@ -0,0 +1,278 @@
+use crate::{
+    src_code::{ColumnKind, Span, SrcFile, byte_column_of, column_of},
+    uoffset32,
+};
+
+/// Encoding of the `character` offsets of a [`Position`], as negotiated through the
+/// `general.positionEncodings` client capability
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum PositionEncoding {
+    Utf8,
+
+    /// Mandatory encoding every server has to support
+    #[default]
+    Utf16,
+
+    Utf32,
+}
+
+impl PositionEncoding {
+    #[must_use]
+    #[inline]
+    pub const fn as_str(self) -> &'static str {
+        return match self {
+            Self::Utf8 => "utf-8",
+            Self::Utf16 => "utf-16",
+            Self::Utf32 => "utf-32",
+        };
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn from_name(encoding: &str) -> Option<Self> {
+        return match encoding {
+            "utf-8" => Some(Self::Utf8),
+            "utf-16" => Some(Self::Utf16),
+            "utf-32" => Some(Self::Utf32),
+            _ => None,
+        };
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn column_kind(self) -> ColumnKind {
+        return match self {
+            Self::Utf8 => ColumnKind::Byte,
+            Self::Utf16 => ColumnKind::Utf16,
+            Self::Utf32 => ColumnKind::Char,
+        };
+    }
+}
+
+impl core::fmt::Display for PositionEncoding {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return write!(f, "{}", self.as_str());
+    }
+}
+
+/// Zero-based line and character offset, with characters counted in a [`PositionEncoding`]
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    pub line: uoffset32,
+    pub character: uoffset32,
+}
+
+impl core::fmt::Display for Position {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return write!(f, "{}:{}", self.line, self.character);
+    }
+}
+
+impl Position {
+    #[must_use]
+    #[inline(always)]
+    pub const fn new(line: uoffset32, character: uoffset32) -> Self {
+        return Self { line, character };
+    }
+
+    /// Position of the byte `offset` of `src`
+    pub fn from_offset(
+        src: &SrcFile,
+        offset: uoffset32,
+        encoding: PositionEncoding,
+    ) -> Result<Self, PositionError> {
+        let Some(line) = src.line_index(offset) else {
+            return Err(PositionError::OffsetOutOfBounds { offset, len: src.len() });
+        };
+        let line_start = src.line_starts()[line as usize];
+        let line_text = &src.code()[line_start as usize..];
+        let Some(character) = column_of(line_text, offset - line_start, encoding.column_kind())
+        else {
+            return Err(PositionError::NotCharBoundary { offset });
+        };
+        return Ok(Self { line, character });
+    }
+
+    /// Byte offset of this position in `src`
+    pub fn to_offset(
+        self,
+        src: &SrcFile,
+        encoding: PositionEncoding,
+    ) -> Result<uoffset32, PositionError> {
+        let Some(line) = src.line(self.line) else {
+            return Err(PositionError::LineOutOfBounds {
+                line: self.line,
+                lines_count: src.lines_count(),
+            });
+        };
+        let line_text = &src.code()[line.start as usize..line.end as usize];
+        let kind = encoding.column_kind();
+        if let Some(byte_column) = byte_column_of(line_text, self.character, kind) {
+            return Ok(line.start + byte_column);
+        }
+
+        return match column_of(line_text, line_text.len() as uoffset32, kind) {
+            Some(line_len) if self.character > line_len => {
+                Err(PositionError::CharacterOutOfBounds { position: self, line_len })
+            }
+            Some(_) | None => Err(PositionError::InsideCharacter { position: self }),
+        };
+    }
+}
+
+/// Zero-based, end-exclusive range of [`Position`]s
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl core::fmt::Display for Range {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return write!(f, "{}-{}", self.start, self.end);
+    }
+}
+
+impl Range {
+    #[must_use]
+    #[inline(always)]
+    pub const fn new(start: Position, end: Position) -> Self {
+        return Self { start, end };
+    }
+
+    /// Range covered by `span` in `src`
+    pub fn from_span(
+        src: &SrcFile,
+        span: Span,
+        encoding: PositionEncoding,
+    ) -> Result<Self, PositionError> {
+        let start = Position::from_offset(src, span.start(), encoding)?;
+        let end = Position::from_offset(src, span.end(), encoding)?;
+        return Ok(Self { start, end });
+    }
+
+    /// Span covered by this range in `src`
+    pub fn to_span(self, src: &SrcFile, encoding: PositionEncoding) -> Result<Span, PositionError> {
+        let start = self.start.to_offset(src, encoding)?;
+        let end = self.end.to_offset(src, encoding)?;
+        let Some(span) = Span::new(start, end) else {
+            return Err(PositionError::InvertedRange { range: self });
+        };
+        return Ok(span);
+    }
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum PositionError {
+    OffsetOutOfBounds {
+        offset: uoffset32,
+        len: uoffset32,
+    },
+    NotCharBoundary {
+        offset: uoffset32,
+    },
+    LineOutOfBounds {
+        line: uoffset32,
+        lines_count: uoffset32,
+    },
+    CharacterOutOfBounds {
+        position: Position,
+        line_len: uoffset32,
+    },
+
+    /// The position points in between the code units of a character, e.g.: in between a UTF-16
+    /// surrogate pair
+    InsideCharacter {
+        position: Position,
+    },
+
+    InvertedRange {
+        range: Range,
+    },
+}
+
+impl core::fmt::Display for PositionError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return match self {
+            Self::OffsetOutOfBounds { offset, len } => {
+                write!(f, "offset {offset} is past the end of the file ({len} bytes)")
+            }
+            Self::NotCharBoundary { offset } => {
+                write!(f, "offset {offset} is not on a character boundary")
+            }
+            Self::LineOutOfBounds { line, lines_count } => {
+                write!(f, "line {line} is past the end of the file ({lines_count} lines)")
+            }
+            Self::CharacterOutOfBounds { position, line_len } => write!(
+                f,
+                "position {position} is past the end of line {} ({line_len} characters)",
+                position.line
+            ),
+            Self::InsideCharacter { position } => {
+                write!(f, "position {position} is inside of a character")
+            }
+            Self::InvertedRange { range } => {
+                write!(f, "range {range} ends before it starts")
+            }
+        };
+    }
+}
+
+#[expect(clippy::missing_trait_methods, reason = "the default implementations are fine")]
+impl core::error::Error for PositionError {}
+
+#[expect(clippy::unwrap_used)]
+#[cfg(test)]
+#[rustfmt::skip]
+mod tests {
+    mod _0_1_2_functionality {
+        use crate::{
+            lsp::{Position, PositionEncoding, PositionError, Range},
+            src_code::{Span, SrcFile},
+        };
+
+        #[test]
+        fn positions() {
+            // 'e' with acute accent is 2 bytes in UTF-8, the crab is 4 bytes in UTF-8 and 2 code units in UTF-16
+            let src = SrcFile::new("test".to_owned(), "a\u{e9}\u{1f980}b\r\nc\n".to_owned()).unwrap();
+
+            let offset = 7;
+            assert_eq!(Position::from_offset(&src, offset, PositionEncoding::Utf8), Ok(Position::new(0, 7)));
+            assert_eq!(Position::from_offset(&src, offset, PositionEncoding::Utf16), Ok(Position::new(0, 4)));
+            assert_eq!(Position::from_offset(&src, offset, PositionEncoding::Utf32), Ok(Position::new(0, 3)));
+            assert_eq!(Position::new(0, 4).to_offset(&src, PositionEncoding::Utf16), Ok(7));
+            assert_eq!(Position::new(0, 3).to_offset(&src, PositionEncoding::Utf32), Ok(7));
+            assert_eq!(Position::new(1, 1).to_offset(&src, PositionEncoding::Utf16), Ok(11));
+            assert_eq!(Position::new(2, 0).to_offset(&src, PositionEncoding::Utf16), Ok(12));
+
+            assert_eq!(Position::from_offset(&src, 2, PositionEncoding::Utf16), Err(PositionError::NotCharBoundary { offset: 2 }));
+            assert_eq!(Position::from_offset(&src, 13, PositionEncoding::Utf16), Err(PositionError::OffsetOutOfBounds { offset: 13, len: 12 }));
+            assert_eq!(
+                Position::new(3, 0).to_offset(&src, PositionEncoding::Utf16),
+                Err(PositionError::LineOutOfBounds { line: 3, lines_count: 3 })
+            );
+            assert_eq!(
+                Position::new(0, 6).to_offset(&src, PositionEncoding::Utf16),
+                Err(PositionError::CharacterOutOfBounds { position: Position::new(0, 6), line_len: 5 })
+            );
+            assert_eq!(
+                Position::new(0, 3).to_offset(&src, PositionEncoding::Utf16),
+                Err(PositionError::InsideCharacter { position: Position::new(0, 3) })
+            );
+            assert_eq!(PositionEncoding::from_name("utf-16"), Some(PositionEncoding::Utf16));
+            assert_eq!(PositionEncoding::default().as_str(), "utf-16");
+        }
+
+        #[test]
+        fn ranges() {
+            let src = SrcFile::new("test".to_owned(), "let \u{1f980} = 1;\nlet b = 2;".to_owned()).unwrap();
+            let span = Span::new(4, 19).unwrap();
+            let range = Range::from_span(&src, span, PositionEncoding::Utf16).unwrap();
+            assert_eq!(range, Range::new(Position::new(0, 4), Position::new(1, 5)));
+            assert_eq!(range.to_span(&src, PositionEncoding::Utf16), Ok(span));
+
+            let inverted = Range::new(Position::new(1, 0), Position::new(0, 0));
+            assert_eq!(inverted.to_span(&src, PositionEncoding::Utf16), Err(PositionError::InvertedRange { range: inverted }));
+        }
+    }
+}
@@ -1,4 +1,17 @@
-use crate::{ascii, uoffset32};
+use crate::{
+    ascii, ioffset, ioffset8, ioffset16, ioffset32, ioffset64, uoffset, uoffset8, uoffset16,
+    uoffset32, uoffset64, utf32,
+};
+use alloc::collections::BTreeMap;
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64::{
+    __m128i, __m256i, _mm_cmpeq_epi8, _mm_loadu_si128, _mm_movemask_epi8, _mm_or_si128,
+    _mm_set1_epi8, _mm256_cmpeq_epi8, _mm256_loadu_si256, _mm256_movemask_epi8, _mm256_or_si256,
+    _mm256_set1_epi8,
+};
+#[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+use std::os::fd::AsRawFd as _;
+use std::{fs::File, io::Read as _, path::Path};
 
 pub const LF: ascii = b'\n';
 pub const CR: ascii = b'\r';
@@ -17,34 +30,120 @@ impl LineEndLen {
     pub const CR: Self = Self::LF;
 }
 
-// IDEA(stefano): make generic over the type of start and end
-    // - constrain the generic type to be number-like
-#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
-pub struct Span {
+mod sealed {
+    /// Only implemented for primitive integers of 1, 2, 4 or 8 bytes, since spans reinterpret their
+    /// bits in order to be generic and usable in constant contexts at the same time
+    #[expect(unnameable_types, reason = "implementations outside of the crate are not allowed")]
+    pub trait Sealed {}
+}
+
+/// Integer types that can be used as [`Span`] offsets, only implemented for the `uoffset*` and
+/// `ioffset*` types
+pub trait SpanIndex:
+    sealed::Sealed + Copy + Default + core::fmt::Debug + core::fmt::Display + core::hash::Hash + Ord
+{
+    const MIN: Self;
+    const MAX: Self;
+    const IS_SIGNED: bool;
+}
+
+macro_rules! impl_span_index {
+    ($($typ:ty => $signed:expr),+ $(,)?) => {
+        $(
+            impl sealed::Sealed for $typ {}
+
+            impl SpanIndex for $typ {
+                const MIN: Self = <$typ>::MIN;
+                const MAX: Self = <$typ>::MAX;
+                const IS_SIGNED: bool = $signed;
+            }
+        )+
+    };
+}
+
+impl_span_index!(
+    uoffset8 => false,
+    uoffset16 => false,
+    uoffset32 => false,
+    uoffset64 => false,
+    uoffset => false,
+    ioffset8 => true,
+    ioffset16 => true,
+    ioffset32 => true,
+    ioffset64 => true,
+    ioffset => true,
+);
+
+/// Common integer type that every [`SpanIndex`] can be losslessly converted to
+type WideIndex = i128;
+
+#[must_use]
+#[inline(always)]
+const fn widen<I: SpanIndex>(index: I) -> WideIndex {
+    const { assert!(matches!(size_of::<I>(), 1 | 2 | 4 | 8), "unsupported index size") };
+    let index_ptr = &raw const index;
+    // SAFETY: `sealed::Sealed` is only implemented for 1, 2, 4 and 8 bytes integers
+    #[rustfmt::skip]
+    return match (size_of::<I>(), I::IS_SIGNED) {
+        (1, false) => unsafe { *index_ptr.cast::<u8>() as WideIndex },
+        (2, false) => unsafe { *index_ptr.cast::<u16>() as WideIndex },
+        (4, false) => unsafe { *index_ptr.cast::<u32>() as WideIndex },
+        (8, false) => unsafe { *index_ptr.cast::<u64>() as WideIndex },
+        (1, true)  => unsafe { *index_ptr.cast::<i8>() as WideIndex },
+        (2, true)  => unsafe { *index_ptr.cast::<i16>() as WideIndex },
+        (4, true)  => unsafe { *index_ptr.cast::<i32>() as WideIndex },
+        (8, true)  => unsafe { *index_ptr.cast::<i64>() as WideIndex },
+        _ => unreachable!(),
+    };
+}
+
+/// Returns [`None`] if `value` does not fit in `I`
+#[must_use]
+#[inline(always)]
+const fn checked_narrow<I: SpanIndex>(value: WideIndex) -> Option<I> {
+    const { assert!(matches!(size_of::<I>(), 1 | 2 | 4 | 8), "unsupported index size") };
+    if value < widen(I::MIN) || value > widen(I::MAX) {
+        return None;
+    }
+
+    let mut index = I::MIN;
+    let index_ptr = &raw mut index;
+    // SAFETY: `sealed::Sealed` is only implemented for 1, 2, 4 and 8 bytes integers
+    match size_of::<I>() {
+        1 => unsafe { *index_ptr.cast::<u8>() = value as u8 },
+        2 => unsafe { *index_ptr.cast::<u16>() = value as u16 },
+        4 => unsafe { *index_ptr.cast::<u32>() = value as u32 },
+        8 => unsafe { *index_ptr.cast::<u64>() = value as u64 },
+        _ => unreachable!(),
+    }
+    return Some(index);
+}
+
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Span<I: SpanIndex = uoffset32> {
     /// Inclusive and less that or equal to [`Self::end()`]
-    pub(crate) start: uoffset32,
+    pub(crate) start: I,
 
     /// Non inclusive and greater than [`Self::start()`]
-    pub(crate) end: uoffset32,
+    pub(crate) end: I,
 }
 
-// TODO(stefano): add debug_assert/panic/checked/unchecked setters for `start` and `end`
-impl Span {
+impl<I: SpanIndex> Span<I> {
     /// # Safety
     ///
     /// `start` must be less than or equal to `end`
     #[must_use]
     #[inline(always)]
     #[track_caller]
-    pub const unsafe fn new_unchecked(start: uoffset32, end: uoffset32) -> Self {
-        debug_assert!(start <= end, "`start` must be less than or equal to `end`");
+    pub const unsafe fn new_unchecked(start: I, end: I) -> Self {
+        debug_assert!(widen(start) <= widen(end), "`start` must be less than or equal to `end`");
         return Self { start, end };
     }
 
     #[must_use]
     #[inline]
-    pub const fn new(start: uoffset32, end: uoffset32) -> Option<Self> {
-        if start > end {
+    pub const fn new(start: I, end: I) -> Option<Self> {
+        if widen(start) > widen(end) {
             return None;
         }
         return Some(Self { start, end });
@@ -52,36 +151,3341 @@ impl Span {
 
     #[must_use]
     #[inline(always)]
-    pub const fn start(self) -> uoffset32 {
+    pub const fn start(self) -> I {
         return self.start;
     }
 
     #[must_use]
     #[inline(always)]
-    pub const fn end(self) -> uoffset32 {
+    pub const fn end(self) -> I {
         return self.end;
     }
 }
 
+// setters
+impl<I: SpanIndex> Span<I> {
+    /// # Safety
+    ///
+    /// `start` must be less than or equal to [`Self::end()`]
+    #[inline(always)]
+    #[track_caller]
+    pub const unsafe fn set_start_unchecked(&mut self, start: I) {
+        debug_assert!(
+            widen(start) <= widen(self.end),
+            "`start` must be less than or equal to `end`"
+        );
+        self.start = start;
+    }
+
+    /// # Panics
+    ///
+    /// If `start` is greater than [`Self::end()`]
+    #[inline]
+    #[track_caller]
+    pub const fn set_start(&mut self, start: I) {
+        assert!(widen(start) <= widen(self.end), "`start` must be less than or equal to `end`");
+        self.start = start;
+    }
+
+    /// Returns [`None`] if `start` is greater than [`Self::end()`]
+    #[must_use]
+    #[inline]
+    pub const fn with_start(self, start: I) -> Option<Self> {
+        return Self::new(start, self.end);
+    }
+
+    /// # Safety
+    ///
+    /// `end` must be greater than or equal to [`Self::start()`]
+    #[inline(always)]
+    #[track_caller]
+    pub const unsafe fn set_end_unchecked(&mut self, end: I) {
+        debug_assert!(
+            widen(self.start) <= widen(end),
+            "`end` must be greater than or equal to `start`"
+        );
+        self.end = end;
+    }
+
+    /// # Panics
+    ///
+    /// If `end` is less than [`Self::start()`]
+    #[inline]
+    #[track_caller]
+    pub const fn set_end(&mut self, end: I) {
+        assert!(widen(self.start) <= widen(end), "`end` must be greater than or equal to `start`");
+        self.end = end;
+    }
+
+    /// Returns [`None`] if `end` is less than [`Self::start()`]
+    #[must_use]
+    #[inline]
+    pub const fn with_end(self, end: I) -> Option<Self> {
+        return Self::new(self.start, end);
+    }
+}
+
+// algebra
+impl<I: SpanIndex> Span<I> {
+    /// Empty span at `offset`
+    #[must_use]
+    #[inline(always)]
+    pub const fn empty(offset: I) -> Self {
+        return Self { start: offset, end: offset };
+    }
+
+    /// Returns [`None`] if the length does not fit in `I`, which can only happen for signed
+    /// indices
+    #[must_use]
+    #[inline(always)]
+    pub const fn checked_len(self) -> Option<I> {
+        return checked_narrow(widen(self.end) - widen(self.start));
+    }
+
+    /// # Panics
+    ///
+    /// If the length does not fit in `I`, which can only happen for signed indices
+    #[must_use]
+    #[inline(always)]
+    #[track_caller]
+    pub const fn len(self) -> I {
+        let Some(len) = self.checked_len() else {
+            panic!("span length overflow");
+        };
+        return len;
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn is_empty(self) -> bool {
+        return widen(self.start) == widen(self.end);
+    }
+
+    /// Smallest span covering both `self` and `other`, including any gap between them
+    #[must_use]
+    #[inline]
+    pub const fn join(self, other: Self) -> Self {
+        let start = if widen(self.start) < widen(other.start) { self.start } else { other.start };
+        let end = if widen(self.end) > widen(other.end) { self.end } else { other.end };
+        return Self { start, end };
+    }
+
+    /// Returns [`None`] if `self` and `other` neither overlap nor touch
+    #[must_use]
+    #[inline]
+    pub const fn intersect(self, other: Self) -> Option<Self> {
+        let start = if widen(self.start) > widen(other.start) { self.start } else { other.start };
+        let end = if widen(self.end) < widen(other.end) { self.end } else { other.end };
+        return Self::new(start, end);
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn contains(self, offset: I) -> bool {
+        let wide_offset = widen(offset);
+        return widen(self.start) <= wide_offset && wide_offset < widen(self.end);
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn contains_span(self, other: Self) -> bool {
+        return widen(self.start) <= widen(other.start) && widen(other.end) <= widen(self.end);
+    }
+
+    /// Whether `self` and `other` share at least one offset
+    #[must_use]
+    #[inline(always)]
+    pub const fn overlaps(self, other: Self) -> bool {
+        return widen(self.start) < widen(other.end) && widen(other.start) < widen(self.end);
+    }
+
+    /// Whether `self` ends before or where `other` starts
+    #[must_use]
+    #[inline(always)]
+    pub const fn is_before(self, other: Self) -> bool {
+        return widen(self.end) <= widen(other.start);
+    }
+
+    /// Whether `self` starts after or where `other` ends
+    #[must_use]
+    #[inline(always)]
+    pub const fn is_after(self, other: Self) -> bool {
+        return widen(other.end) <= widen(self.start);
+    }
+
+    /// Moves the start forward by `start_amount` and the end backward by `end_amount`, returning
+    /// [`None`] if the span would end before it starts
+    #[must_use]
+    #[inline]
+    pub const fn shrink(self, start_amount: I, end_amount: I) -> Option<Self> {
+        let wide_start = widen(self.start) + widen(start_amount);
+        let wide_end = widen(self.end) - widen(end_amount);
+        if wide_start > wide_end {
+            return None;
+        }
+        let (Some(start), Some(end)) = (checked_narrow(wide_start), checked_narrow(wide_end))
+        else {
+            return None;
+        };
+        return Some(Self { start, end });
+    }
+
+    /// Moves the start backward by `start_amount` and the end forward by `end_amount`, returning
+    /// [`None`] on overflow or if the span would end before it starts
+    #[must_use]
+    #[inline]
+    pub const fn grow(self, start_amount: I, end_amount: I) -> Option<Self> {
+        let wide_start = widen(self.start) - widen(start_amount);
+        let wide_end = widen(self.end) + widen(end_amount);
+        if wide_start > wide_end {
+            return None;
+        }
+        let (Some(start), Some(end)) = (checked_narrow(wide_start), checked_narrow(wide_end))
+        else {
+            return None;
+        };
+        return Some(Self { start, end });
+    }
+
+    /// Moves the whole span by `amount`, returning [`None`] on overflow
+    #[must_use]
+    #[inline]
+    pub const fn shift_by(self, amount: ioffset64) -> Option<Self> {
+        let wide_start = widen(self.start) + amount as WideIndex;
+        let wide_end = widen(self.end) + amount as WideIndex;
+        let (Some(start), Some(end)) = (checked_narrow(wide_start), checked_narrow(wide_end))
+        else {
+            return None;
+        };
+        return Some(Self { start, end });
+    }
+
+    /// Text covered by the span, returning [`None`] if the span is out of bounds of `text` or does
+    /// not start and end on character boundaries
+    #[must_use]
+    #[inline]
+    pub const fn slice(self, text: &str) -> Option<&str> {
+        let (wide_start, wide_end) = (widen(self.start), widen(self.end));
+        if wide_start < 0 || wide_end > text.len() as WideIndex {
+            return None;
+        }
+
+        let (start, end) = (wide_start as usize, wide_end as usize);
+        if !text.is_char_boundary(start) || !text.is_char_boundary(end) {
+            return None;
+        }
+
+        let slice_ptr = unsafe { text.as_ptr().add(start) };
+        let slice_bytes = unsafe { core::slice::from_raw_parts(slice_ptr, end - start) };
+        return Some(unsafe { core::str::from_utf8_unchecked(slice_bytes) });
+    }
+}
+
+// conversions
+impl<I: SpanIndex> Span<I> {
+    /// Converts to a span with different offset types, returning [`None`] if the offsets do not
+    /// fit in `J`
+    #[must_use]
+    #[inline]
+    pub const fn checked_cast<J: SpanIndex>(self) -> Option<Span<J>> {
+        let (Some(start), Some(end)) =
+            (checked_narrow(widen(self.start)), checked_narrow(widen(self.end)))
+        else {
+            return None;
+        };
+        return Some(Span { start, end });
+    }
+}
+
+macro_rules! impl_span_from {
+    ($($from:ty => $($into:ty),+;)+) => {
+        $($(
+            impl From<Span<$from>> for Span<$into> {
+                #[inline(always)]
+                fn from(span: Span<$from>) -> Self {
+                    return Self { start: span.start.into(), end: span.end.into() };
+                }
+            }
+        )+)+
+    };
+}
+
+impl_span_from!(
+    uoffset8 => uoffset16, uoffset32, uoffset64, uoffset, ioffset16, ioffset32, ioffset64, ioffset;
+    uoffset16 => uoffset32, uoffset64, uoffset, ioffset32, ioffset64;
+    uoffset32 => uoffset64, ioffset64;
+    ioffset8 => ioffset16, ioffset32, ioffset64, ioffset;
+    ioffset16 => ioffset32, ioffset64, ioffset;
+    ioffset32 => ioffset64;
+);
+
+// compact spans
+/// [`Span`] packed in 32 bits
+///
+/// Spans with a start of up to [`CompactSpan::MAX_INLINE_START`] (8 MiB) and a length of up to
+/// [`CompactSpan::MAX_INLINE_LEN`] are stored inline, any other span is stored in a
+/// [`SpanInterner`]: `0 start:23 len:8` for inline spans, `1 index:31` for interned spans
+///
+/// Inline spans don't know which file they are in, so the spans of other files are interned
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub struct CompactSpan(pub(crate) u32);
+
+impl CompactSpan {
+    const INTERNED_BIT: u32 = 1 << 31;
+    const LEN_BITS: u32 = 8;
+    const LEN_MASK: u32 = (1 << Self::LEN_BITS) - 1;
+
+    pub const MAX_INLINE_START: uoffset32 = (1 << 23) - 1;
+    pub const MAX_INLINE_LEN: uoffset32 = Self::LEN_MASK;
+    pub const MAX_INTERNED: uoffset32 = Self::INTERNED_BIT - 1;
+
+    /// Returns [`None`] if `span` is too big to be stored inline
+    #[must_use]
+    #[inline]
+    pub const fn inline(span: Span) -> Option<Self> {
+        let len = span.len();
+        if span.start > Self::MAX_INLINE_START || len > Self::MAX_INLINE_LEN {
+            return None;
+        }
+        return Some(Self((span.start << Self::LEN_BITS) | len));
+    }
+
+    /// Returns [`None`] if the span is interned
+    #[must_use]
+    #[inline]
+    pub const fn inline_span(self) -> Option<Span> {
+        if self.is_interned() {
+            return None;
+        }
+        let start = self.0 >> Self::LEN_BITS;
+        let len = self.0 & Self::LEN_MASK;
+        return Some(Span { start, end: start + len });
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn is_interned(self) -> bool {
+        return self.0 & Self::INTERNED_BIT != 0;
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn to_bits(self) -> u32 {
+        return self.0;
+    }
+}
+
+impl TryFrom<Span> for CompactSpan {
+    type Error = Span;
+
+    /// Fails with the original span if it cannot be stored inline
+    #[inline]
+    fn try_from(span: Span) -> Result<Self, Self::Error> {
+        return Self::inline(span).ok_or(span);
+    }
+}
+
+/// Storage for the [`CompactSpan`]s that don't fit inline or that are in another file than the one
+/// of the interner
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub struct SpanInterner {
+    pub(crate) file: FileId,
+    pub(crate) spans: Vec<FileSpan>,
+    pub(crate) indices: BTreeMap<FileSpan, uoffset32>,
+}
+
+impl SpanInterner {
+    /// Interner storing the spans in `file` inline
+    #[must_use]
+    #[inline(always)]
+    pub const fn new(file: FileId) -> Self {
+        return Self { file, spans: Vec::new(), indices: BTreeMap::new() };
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn file(&self) -> FileId {
+        return self.file;
+    }
+
+    /// Stores `span` inline if possible, interning the same span only once
+    ///
+    /// # Panics
+    ///
+    /// If more than [`CompactSpan::MAX_INTERNED`] spans are interned
+    pub fn intern(&mut self, span: FileSpan) -> CompactSpan {
+        if span.file == self.file
+            && let Some(compact) = CompactSpan::inline(span.span)
+        {
+            return compact;
+        }
+        if let Some(&index) = self.indices.get(&span) {
+            return CompactSpan(CompactSpan::INTERNED_BIT | index);
+        }
+
+        let index = self.spans.len() as uoffset32;
+        assert!(index <= CompactSpan::MAX_INTERNED, "too many interned spans");
+        self.spans.push(span);
+        let _ = self.indices.insert(span, index);
+        return CompactSpan(CompactSpan::INTERNED_BIT | index);
+    }
+
+    /// Returns [`None`] if `compact` is interned past the spans of this interner
+    ///
+    /// Spans interned in another interner are not detected, and resolve to unrelated spans
+    #[must_use]
+    pub fn resolve(&self, compact: CompactSpan) -> Option<FileSpan> {
+        if !compact.is_interned() {
+            let span = compact.inline_span()?;
+            return Some(FileSpan { file: self.file, span });
+        }
+        let index = compact.0 & !CompactSpan::INTERNED_BIT;
+        return self.spans.get(index as usize).copied();
+    }
+
+    /// Number of interned spans
+    #[must_use]
+    #[inline(always)]
+    pub const fn len(&self) -> usize {
+        return self.spans.len();
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn is_empty(&self) -> bool {
+        return self.spans.is_empty();
+    }
+}
+
 pub type Line = Span;
 
-#[expect(clippy::should_panic_without_expect, unused_imports)]
-#[cfg(test)]
-#[rustfmt::skip]
-mod tests {
-    mod _0_1_0_functionality {
-        use crate::{src_code::Span, test_assert};
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum LineEnd {
+    LF,
+    CRLF,
+    CR,
 
-        const _: () = test_assert!(unsafe { Span::new_unchecked(0, 0) }, == Span { start: 0, end: 0 });
+    /// Last line of the code, not followed by any line terminator
+    None,
+}
 
-        #[should_panic]
-        #[test]
-        const fn span_new_unchecked_should_panic() {
-            let _ = unsafe { Span::new_unchecked(1, 0) };
+impl LineEnd {
+    #[must_use]
+    #[inline]
+    pub const fn len(self) -> uoffset32 {
+        return match self {
+            Self::LF => LineEndLen::LF as uoffset32,
+            Self::CRLF => LineEndLen::CRLF as uoffset32,
+            Self::CR => LineEndLen::CR as uoffset32,
+            Self::None => 0,
+        };
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn as_str(self) -> &'static str {
+        return match self {
+            Self::LF => LF_STR,
+            Self::CRLF => CRLF_STR,
+            Self::CR => CR_STR,
+            Self::None => "",
+        };
+    }
+}
+
+/// Iterator over the lines of some code, yielding the span of each line's content, excluding its
+/// terminator, together with the kind of the terminator.
+///
+/// Just like [`line_starts`], a terminator at the end of the code is followed by an empty line.
+#[derive(Clone, Debug)]
+pub struct Lines<'code> {
+    code: &'code str,
+    offset: uoffset32,
+    done: bool,
+}
+
+impl<'code> Lines<'code> {
+    #[must_use]
+    #[inline(always)]
+    pub const fn new(code: &'code str) -> Self {
+        return Self { code, offset: 0, done: false };
+    }
+}
+
+#[expect(clippy::missing_trait_methods, reason = "the default implementations are fine")]
+impl Iterator for Lines<'_> {
+    type Item = (Line, LineEnd);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
         }
 
-        const _: () = test_assert!(Span::new(0, 0), == Some(Span { start: 0, end: 0 }));
-        const _: () = test_assert!(Span::new(1, 0), == None);
+        let bytes = self.code.as_bytes();
+        let start = self.offset;
+        let mut index = start as usize;
+        while index < bytes.len() {
+            let line_end = match bytes[index] {
+                LF => LineEnd::LF,
+                CR => match bytes.get(index + 1) {
+                    Some(&LF) => LineEnd::CRLF,
+                    Some(_) | None => LineEnd::CR,
+                },
+                _ => {
+                    index += 1;
+                    continue;
+                }
+            };
+            let end = index as uoffset32;
+            self.offset = end + line_end.len();
+            return Some((Line { start, end }, line_end));
+        }
+
+        self.done = true;
+        return Some((Line { start, end: bytes.len() as uoffset32 }, LineEnd::None));
+    }
+}
+
+#[must_use]
+#[inline(always)]
+pub const fn lines(code: &str) -> Lines<'_> {
+    return Lines::new(code);
+}
+
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub struct LineEndCounts {
+    pub lf: uoffset32,
+    pub crlf: uoffset32,
+    pub cr: uoffset32,
+}
+
+impl LineEndCounts {
+    #[must_use]
+    pub fn of(code: &str) -> Self {
+        let mut counts = Self::default();
+        for (_, line_end) in lines(code) {
+            match line_end {
+                LineEnd::LF => counts.lf += 1,
+                LineEnd::CRLF => counts.crlf += 1,
+                LineEnd::CR => counts.cr += 1,
+                LineEnd::None => {}
+            }
+        }
+        return counts;
+    }
+
+    /// Whether more than one kind of line terminator is used
+    #[must_use]
+    #[inline]
+    pub const fn is_mixed(self) -> bool {
+        let kinds = (self.lf > 0) as u8 + (self.crlf > 0) as u8 + (self.cr > 0) as u8;
+        return kinds > 1;
+    }
+
+    /// Most used line terminator, preferring `LF`, then `CRLF` in case of ties, and [`None`] if
+    /// there are no line terminators
+    #[must_use]
+    #[inline]
+    pub const fn dominant(self) -> Option<LineEnd> {
+        if self.lf == 0 && self.crlf == 0 && self.cr == 0 {
+            return None;
+        }
+        if self.lf >= self.crlf && self.lf >= self.cr {
+            return Some(LineEnd::LF);
+        }
+        if self.crlf >= self.cr {
+            return Some(LineEnd::CRLF);
+        }
+        return Some(LineEnd::CR);
+    }
+}
+
+/// Maps offsets of code normalized by [`normalize_line_endings`] back to the original code, and
+/// vice versa
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub struct LineEndRemap {
+    /// Offsets, in the normalized code, of the `LF`s that were originally `CRLF`s
+    crlf_offsets: Vec<uoffset32>,
+}
+
+impl LineEndRemap {
+    #[must_use]
+    #[inline(always)]
+    pub const fn is_identity(&self) -> bool {
+        return self.crlf_offsets.is_empty();
+    }
+
+    /// Offsets in the middle of a `CRLF` get mapped to the `CR`
+    #[must_use]
+    pub fn original_offset(&self, normalized_offset: uoffset32) -> uoffset32 {
+        let removed =
+            self.crlf_offsets.partition_point(|&offset| return offset < normalized_offset);
+        return normalized_offset + removed as uoffset32;
+    }
+
+    #[must_use]
+    pub fn original_span(&self, normalized_span: Span) -> Span {
+        let start = self.original_offset(normalized_span.start);
+        let end = self.original_offset(normalized_span.end);
+        return Span { start, end };
+    }
+
+    /// Offsets in the middle of a `CRLF` get mapped to the resulting `LF`
+    #[must_use]
+    pub fn normalized_offset(&self, original_offset: uoffset32) -> uoffset32 {
+        // the `LF` of the i-th `CRLF` is at `crlf_offsets[i] + i + 1` in the original code
+        let mut low = 0;
+        let mut high = self.crlf_offsets.len();
+        while low < high {
+            let middle = low + (high - low) / 2;
+            let original_lf_offset = self.crlf_offsets[middle] + middle as uoffset32 + 1;
+            if original_lf_offset <= original_offset {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        return original_offset - low as uoffset32;
+    }
+
+    #[must_use]
+    pub fn normalized_span(&self, original_span: Span) -> Span {
+        let start = self.normalized_offset(original_span.start);
+        let end = self.normalized_offset(original_span.end);
+        return Span { start, end };
+    }
+}
+
+/// Rewrites all line terminators to `LF`, returning the normalized code and the mapping needed to
+/// report spans of the normalized code against the original code
+#[must_use]
+pub fn normalize_line_endings(code: &str) -> (String, LineEndRemap) {
+    let mut normalized = String::with_capacity(code.len());
+    let mut remap = LineEndRemap::default();
+    for (line, line_end) in lines(code) {
+        normalized.push_str(&code[line.start as usize..line.end as usize]);
+        match line_end {
+            LineEnd::LF | LineEnd::CR => normalized.push_str(LF_STR),
+            LineEnd::CRLF => {
+                remap.crlf_offsets.push(normalized.len() as uoffset32);
+                normalized.push_str(LF_STR);
+            }
+            LineEnd::None => {}
+        }
+    }
+    return (normalized, remap);
+}
+
+/// 1-based line and column pair, with the column measured in bytes unless specified otherwise by
+/// a [`ColumnKind`]
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LineColumn {
+    pub line: uoffset32,
+    pub column: uoffset32,
+}
+
+impl core::fmt::Display for LineColumn {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return write!(f, "{}:{}", self.line, self.column);
+    }
+}
+
+/// Computes the offsets of the first byte of every line, treating `LF`, `CRLF` and lone `CR` as
+/// line terminators.
+///
+/// The first line always starts at offset `0`, and a terminator at the end of `code` is followed
+/// by an empty line starting at `code.len()`.
+#[must_use]
+pub fn line_starts(code: &str) -> Vec<uoffset32> {
+    let bytes = code.as_bytes();
+    let mut starts = vec![0];
+    let mut index = 0;
+    while index < bytes.len() {
+        let line_end_len = match bytes[index] {
+            LF => LineEndLen::LF,
+            CR => match bytes.get(index + 1) {
+                Some(&LF) => LineEndLen::CRLF,
+                Some(_) | None => LineEndLen::CR,
+            },
+            _ => {
+                index += 1;
+                continue;
+            }
+        };
+        index += line_end_len as usize;
+        starts.push(index as uoffset32);
+    }
+    return starts;
+}
+
+/// Strategy used to find the line terminators of some code, all producing the same line starts
+/// as [`line_starts`]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum LineIndexer {
+    /// One byte at a time
+    Scalar,
+
+    /// One machine word at a time
+    Swar,
+
+    #[cfg(target_arch = "x86_64")]
+    Sse2,
+
+    /// Only available on CPUs supporting AVX2
+    #[cfg(target_arch = "x86_64")]
+    Avx2,
+}
+
+impl LineIndexer {
+    /// Fastest indexer supported by the current CPU
+    #[must_use]
+    #[inline]
+    pub fn detect() -> Self {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if std::arch::is_x86_feature_detected!("avx2") {
+                return Self::Avx2;
+            }
+            return Self::Sse2;
+        }
+
+        #[cfg(not(target_arch = "x86_64"))]
+        return Self::Swar;
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn is_supported(self) -> bool {
+        return match self {
+            Self::Scalar | Self::Swar => true,
+            #[cfg(target_arch = "x86_64")]
+            Self::Sse2 => true,
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx2 => std::arch::is_x86_feature_detected!("avx2"),
+        };
+    }
+
+    /// Falls back to [`LineIndexer::Swar`] if this indexer is not supported by the current CPU
+    #[must_use]
+    pub fn line_starts(self, code: &str) -> Vec<uoffset32> {
+        return match self {
+            Self::Scalar => line_starts(code),
+            Self::Swar => line_starts_swar(code),
+            #[cfg(target_arch = "x86_64")]
+            // SAFETY: every `x86_64` CPU supports SSE2
+            Self::Sse2 => unsafe { line_starts_sse2(code) },
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx2 => {
+                if !std::arch::is_x86_feature_detected!("avx2") {
+                    return line_starts_swar(code);
+                }
+                // SAFETY: the CPU was just checked to support AVX2
+                unsafe { line_starts_avx2(code) }
+            }
+        };
+    }
+}
+
+/// Pushes the start of the line after the byte at `index`, if it terminates a line
+#[inline(always)]
+fn push_line_start(bytes: &[ascii], index: usize, starts: &mut Vec<uoffset32>) {
+    match bytes[index] {
+        LF => starts.push(index as uoffset32 + 1),
+        CR if bytes.get(index + 1) != Some(&LF) => starts.push(index as uoffset32 + 1),
+        _ => {}
+    }
+}
+
+/// Pushes the line starts after the bytes of the chunk starting at `chunk_start` with a set bit
+/// in `mask`, where each byte corresponds to `1 << bits_per_byte_log2` bits
+#[inline(always)]
+fn push_masked_line_starts(
+    bytes: &[ascii],
+    chunk_start: usize,
+    mut mask: u64,
+    bits_per_byte_log2: u32,
+    starts: &mut Vec<uoffset32>,
+) {
+    while mask != 0 {
+        let byte = (mask.trailing_zeros() >> bits_per_byte_log2) as usize;
+        push_line_start(bytes, chunk_start + byte, starts);
+        mask &= mask - 1;
+    }
+}
+
+/// [`line_starts`] scanning a machine word at a time
+#[must_use]
+fn line_starts_swar(code: &str) -> Vec<uoffset32> {
+    const CHUNK_LEN: usize = size_of::<u64>();
+    const ONES: u64 = 0x0101_0101_0101_0101;
+    const LOW_BITS: u64 = 0x7F * ONES;
+    const HIGH_BITS: u64 = 0x80 * ONES;
+    const LFS: u64 = LF as u64 * ONES;
+    const CRS: u64 = CR as u64 * ONES;
+
+    let bytes = code.as_bytes();
+    let mut starts = vec![0];
+    let chunks = bytes.chunks_exact(CHUNK_LEN);
+    let remainder_start = bytes.len() - chunks.remainder().len();
+    for (chunk_index, chunk) in chunks.enumerate() {
+        let mut word_bytes = [0; CHUNK_LEN];
+        word_bytes.copy_from_slice(chunk);
+        #[expect(clippy::little_endian_bytes, reason = "the first byte has to be the lowest byte")]
+        let word = u64::from_le_bytes(word_bytes);
+
+        // the high bit of each byte is set if the byte is zero, i.e.: if it matched
+        let lfs = word ^ LFS;
+        let crs = word ^ CRS;
+        let lf_mask = !(((lfs & LOW_BITS) + LOW_BITS) | lfs) & HIGH_BITS;
+        let cr_mask = !(((crs & LOW_BITS) + LOW_BITS) | crs) & HIGH_BITS;
+        push_masked_line_starts(bytes, chunk_index * CHUNK_LEN, lf_mask | cr_mask, 3, &mut starts);
+    }
+    for index in remainder_start..bytes.len() {
+        push_line_start(bytes, index, &mut starts);
+    }
+    return starts;
+}
+
+/// [`line_starts`] scanning 16 bytes at a time
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse2")]
+#[must_use]
+fn line_starts_sse2(code: &str) -> Vec<uoffset32> {
+    const CHUNK_LEN: usize = size_of::<__m128i>();
+
+    let bytes = code.as_bytes();
+    let mut starts = vec![0];
+    let lfs = _mm_set1_epi8(LF as i8);
+    let crs = _mm_set1_epi8(CR as i8);
+    let chunks = bytes.chunks_exact(CHUNK_LEN);
+    let remainder_start = bytes.len() - chunks.remainder().len();
+    for (chunk_index, chunk) in chunks.enumerate() {
+        #[expect(clippy::cast_ptr_alignment, reason = "unaligned load")]
+        let vector = unsafe { _mm_loadu_si128(chunk.as_ptr().cast::<__m128i>()) };
+        let matches = _mm_or_si128(_mm_cmpeq_epi8(vector, lfs), _mm_cmpeq_epi8(vector, crs));
+        let mask = _mm_movemask_epi8(matches) as u32 as u64;
+        push_masked_line_starts(bytes, chunk_index * CHUNK_LEN, mask, 0, &mut starts);
+    }
+    for index in remainder_start..bytes.len() {
+        push_line_start(bytes, index, &mut starts);
+    }
+    return starts;
+}
+
+/// [`line_starts`] scanning 32 bytes at a time
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+#[must_use]
+fn line_starts_avx2(code: &str) -> Vec<uoffset32> {
+    const CHUNK_LEN: usize = size_of::<__m256i>();
+
+    let bytes = code.as_bytes();
+    let mut starts = vec![0];
+    let lfs = _mm256_set1_epi8(LF as i8);
+    let crs = _mm256_set1_epi8(CR as i8);
+    let chunks = bytes.chunks_exact(CHUNK_LEN);
+    let remainder_start = bytes.len() - chunks.remainder().len();
+    for (chunk_index, chunk) in chunks.enumerate() {
+        #[expect(clippy::cast_ptr_alignment, reason = "unaligned load")]
+        let vector = unsafe { _mm256_loadu_si256(chunk.as_ptr().cast::<__m256i>()) };
+        let matches =
+            _mm256_or_si256(_mm256_cmpeq_epi8(vector, lfs), _mm256_cmpeq_epi8(vector, crs));
+        let mask = _mm256_movemask_epi8(matches) as u32 as u64;
+        push_masked_line_starts(bytes, chunk_index * CHUNK_LEN, mask, 0, &mut starts);
+    }
+    for index in remainder_start..bytes.len() {
+        push_line_start(bytes, index, &mut starts);
+    }
+    return starts;
+}
+
+/// Code of a [`SrcFile`], either owned or kept in the read-only memory mapping of its file
+#[derive(Debug)]
+pub(crate) enum SrcCode {
+    Owned(String),
+
+    /// Valid UTF-8 starting at `start`, after the [`Bom`] if any
+    #[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+    Mapped {
+        mmap: Mmap,
+        start: uoffset,
+    },
+}
+
+impl SrcCode {
+    #[must_use]
+    #[inline]
+    pub(crate) const fn as_str(&self) -> &str {
+        return match self {
+            Self::Owned(code) => code.as_str(),
+            #[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+            Self::Mapped { mmap, start } => {
+                let (_, bytes) = mmap.as_bytes().split_at(*start);
+                // SAFETY: validated as UTF-8 by `SrcFile::from_mmap`
+                unsafe { core::str::from_utf8_unchecked(bytes) }
+            }
+        };
+    }
+
+    /// Copies mapped code into an owned buffer first
+    #[must_use]
+    pub(crate) fn to_mut(&mut self) -> &mut String {
+        #[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+        if let Self::Mapped { .. } = self {
+            *self = Self::Owned(self.as_str().to_owned());
+        }
+        return match self {
+            Self::Owned(code) => code,
+            #[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+            Self::Mapped { .. } => unreachable!("mapped code has just been copied"),
+        };
+    }
+}
+
+impl core::ops::Deref for SrcCode {
+    type Target = str;
+
+    #[inline(always)]
+    fn deref(&self) -> &Self::Target {
+        return self.as_str();
+    }
+}
+
+impl Default for SrcCode {
+    #[inline(always)]
+    fn default() -> Self {
+        return Self::Owned(String::new());
+    }
+}
+
+#[expect(clippy::missing_trait_methods, reason = "the default implementations are fine")]
+impl Clone for SrcCode {
+    #[inline]
+    fn clone(&self) -> Self {
+        return Self::Owned(self.as_str().to_owned());
+    }
+}
+
+#[expect(clippy::missing_trait_methods, reason = "the default implementations are fine")]
+impl PartialEq for SrcCode {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        return self.as_str() == other.as_str();
+    }
+}
+
+#[expect(clippy::missing_trait_methods, reason = "the default implementations are fine")]
+impl Eq for SrcCode {}
+
+#[expect(clippy::missing_trait_methods, reason = "the default implementations are fine")]
+impl core::hash::Hash for SrcCode {
+    #[inline]
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub struct SrcFile {
+    pub(crate) name: String,
+    pub(crate) code: SrcCode,
+
+    /// Offsets of the first byte of every line, sorted and always starting with `0`
+    pub(crate) line_starts: Vec<uoffset32>,
+
+    /// Whether the code was preceded by a UTF-8 [`Bom`], which is not part of [`Self::code()`]
+    pub(crate) has_bom: bool,
+}
+
+impl SrcFile {
+    /// Returns [`None`] if `code` is too long to be addressed by a [`Span`]
+    #[must_use]
+    pub fn new(name: String, code: String) -> Option<Self> {
+        if code.len() > uoffset32::MAX as usize {
+            return None;
+        }
+        let line_starts = LineIndexer::detect().line_starts(&code);
+        return Some(Self { name, code: SrcCode::Owned(code), line_starts, has_bom: false });
+    }
+
+    /// Strips a UTF-8 [`Bom`] and replaces each invalid UTF-8 sequence with a
+    /// [`char::REPLACEMENT_CHARACTER`], reporting where they were found
+    pub fn from_bytes(name: String, bytes: &[u8]) -> Result<(Self, Vec<InvalidUtf8>), DecodeError> {
+        let bom = Bom::detect(bytes);
+        let bom_len = match bom {
+            None => 0,
+            Some(Bom::Utf8) => Bom::UTF8.len(),
+            Some(other_bom) => return Err(DecodeError::UnsupportedBom(other_bom)),
+        };
+
+        let mut code = String::with_capacity(bytes.len() - bom_len);
+        let mut invalid_utf8 = Vec::new();
+        let mut offset = bom_len;
+        for chunk in bytes[bom_len..].utf8_chunks() {
+            code.push_str(chunk.valid());
+            offset += chunk.valid().len();
+
+            let invalid = chunk.invalid();
+            if invalid.is_empty() {
+                continue;
+            }
+
+            let code_offset = code.len();
+            code.push(char::REPLACEMENT_CHARACTER);
+            if code.len() > uoffset32::MAX as usize {
+                break;
+            }
+            invalid_utf8.push(InvalidUtf8 {
+                bytes: Span {
+                    start: offset as uoffset32,
+                    end: (offset + invalid.len()) as uoffset32,
+                },
+                span: Span { start: code_offset as uoffset32, end: code.len() as uoffset32 },
+            });
+            offset += invalid.len();
+        }
+
+        if code.len() > uoffset32::MAX as usize {
+            return Err(DecodeError::TooLarge(code.len()));
+        }
+        let line_starts = LineIndexer::detect().line_starts(&code);
+        let file = Self { name, code: SrcCode::Owned(code), line_starts, has_bom: bom.is_some() };
+        return Ok((file, invalid_utf8));
+    }
+
+    /// Like [`Self::from_bytes`], but keeps the code in `mmap` instead of copying it, unless it
+    /// contains invalid UTF-8
+    #[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+    pub fn from_mmap(name: String, mmap: Mmap) -> Result<(Self, Vec<InvalidUtf8>), DecodeError> {
+        let bytes = mmap.as_bytes();
+        let bom = Bom::detect(bytes);
+        let start = match bom {
+            None => 0,
+            Some(Bom::Utf8) => Bom::UTF8.len(),
+            Some(other_bom) => return Err(DecodeError::UnsupportedBom(other_bom)),
+        };
+        let Ok(code) = core::str::from_utf8(&bytes[start..]) else {
+            return Self::from_bytes(name, bytes);
+        };
+        if code.len() > uoffset32::MAX as usize {
+            return Err(DecodeError::TooLarge(code.len()));
+        }
+
+        let line_starts = LineIndexer::detect().line_starts(code);
+        let has_bom = bom.is_some();
+        let file = Self { name, code: SrcCode::Mapped { mmap, start }, line_starts, has_bom };
+        return Ok((file, Vec::new()));
+    }
+
+    /// Whether the code is kept in the memory mapping of its file
+    #[must_use]
+    #[inline(always)]
+    pub const fn is_mapped(&self) -> bool {
+        return match self.code {
+            SrcCode::Owned(_) => false,
+            #[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+            SrcCode::Mapped { .. } => true,
+        };
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn has_bom(&self) -> bool {
+        return self.has_bom;
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub fn name(&self) -> &str {
+        return &self.name;
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub fn code(&self) -> &str {
+        return &self.code;
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub fn lines(&self) -> Lines<'_> {
+        return Lines::new(&self.code);
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub fn line_starts(&self) -> &[uoffset32] {
+        return &self.line_starts;
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn len(&self) -> uoffset32 {
+        return self.code.as_str().len() as uoffset32;
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn lines_count(&self) -> uoffset32 {
+        return self.line_starts.len() as uoffset32;
+    }
+
+    /// 0-based index of the line containing `offset`, where `offset` can point one past the end
+    /// of the code
+    #[must_use]
+    pub fn line_index(&self, offset: uoffset32) -> Option<uoffset32> {
+        if offset > self.len() {
+            return None;
+        }
+        let index = match self.line_starts.binary_search(&offset) {
+            Ok(line_start_index) => line_start_index,
+            Err(next_line_start_index) => next_line_start_index - 1,
+        };
+        return Some(index as uoffset32);
+    }
+
+    /// Span of the line at the 0-based `index`, including its line terminator
+    #[must_use]
+    pub fn line_span(&self, index: uoffset32) -> Option<Span> {
+        let start = *self.line_starts.get(index as usize)?;
+        let end = match self.line_starts.get(index as usize + 1) {
+            Some(&next_line_start) => next_line_start,
+            None => self.len(),
+        };
+        return Some(Span { start, end });
+    }
+
+    /// Span of the line at the 0-based `index`, excluding its line terminator
+    #[must_use]
+    pub fn line(&self, index: uoffset32) -> Option<Line> {
+        let Span { start, mut end } = self.line_span(index)?;
+        let bytes = self.code.as_bytes();
+        if end > start && bytes[end as usize - 1] == LF {
+            end -= 1;
+        }
+        if end > start && bytes[end as usize - 1] == CR {
+            end -= 1;
+        }
+        return Some(Line { start, end });
+    }
+
+    /// Text of the line at the 0-based `index`, excluding its line terminator
+    #[must_use]
+    pub fn line_text(&self, index: uoffset32) -> Option<&str> {
+        let Line { start, end } = self.line(index)?;
+        return Some(&self.code[start as usize..end as usize]);
+    }
+
+    #[must_use]
+    pub fn line_column(&self, offset: uoffset32) -> Option<LineColumn> {
+        let index = self.line_index(offset)?;
+        let line_start = self.line_starts[index as usize];
+        return Some(LineColumn { line: index + 1, column: offset - line_start + 1 });
+    }
+
+    /// Line and column of the start and end of `span`
+    #[must_use]
+    pub fn span_line_columns(&self, span: Span) -> Option<(LineColumn, LineColumn)> {
+        let start = self.line_column(span.start)?;
+        let end = self.line_column(span.end)?;
+        return Some((start, end));
+    }
+
+    /// Line and column of `offset`, with the column measured in units of `kind`
+    #[must_use]
+    pub fn line_column_of(&self, offset: uoffset32, kind: ColumnKind) -> Option<LineColumn> {
+        let index = self.line_index(offset)?;
+        let line_start = self.line_starts[index as usize];
+        let line_text = &self.code[line_start as usize..];
+        let column = column_of(line_text, offset - line_start, kind)?;
+        return Some(LineColumn { line: index + 1, column: column + 1 });
+    }
+
+    /// Offset of the character at `line_column`, with the column measured in units of `kind`
+    #[must_use]
+    pub fn offset_of(&self, line_column: LineColumn, kind: ColumnKind) -> Option<uoffset32> {
+        let index = line_column.line.checked_sub(1)?;
+        let column = line_column.column.checked_sub(1)?;
+        let line = self.line(index)?;
+        let line_text = &self.code[line.start as usize..line.end as usize];
+        let byte_column = byte_column_of(line_text, column, kind)?;
+        return Some(line.start + byte_column);
+    }
+}
+
+// editing
+impl SrcFile {
+    /// Applies `edit`, updating the line starts only around the edited text instead of
+    /// recomputing them for the whole code
+    pub fn apply_edit(&mut self, edit: &TextEdit) -> Result<(), EditError> {
+        let Span { start, end } = edit.span;
+        if end > self.len() {
+            return Err(EditError::OutOfBounds);
+        }
+        if !self.code.is_char_boundary(start as usize) || !self.code.is_char_boundary(end as usize)
+        {
+            return Err(EditError::NotCharBoundary);
+        }
+        let new_len = self.code.len() - edit.span.len() as usize + edit.replacement.len();
+        if new_len > uoffset32::MAX as usize {
+            return Err(EditError::TooLarge);
+        }
+
+        self.code.to_mut().replace_range(start as usize..end as usize, &edit.replacement);
+
+        // a line start depends on the byte before it and the byte at it, so only the line starts
+        // touching the replaced text need to be recomputed
+        let first_affected = self
+            .line_starts
+            .partition_point(|&line_start| {
+                return line_start < start;
+            })
+            .max(1);
+        let first_unaffected = self.line_starts.partition_point(|&line_start| {
+            return line_start <= end;
+        });
+
+        let bytes = self.code.as_bytes();
+        let replacement_end = start as usize + edit.replacement.len();
+        let mut new_line_starts = Vec::new();
+        for offset in (start as usize).max(1)..=replacement_end {
+            let is_line_start = match bytes[offset - 1] {
+                LF => true,
+                CR => bytes.get(offset) != Some(&LF),
+                _ => false,
+            };
+            if is_line_start {
+                new_line_starts.push(offset as uoffset32);
+            }
+        }
+
+        let delta = edit.delta();
+        for line_start in &mut self.line_starts[first_unaffected..] {
+            *line_start = (*line_start as ioffset64 + delta) as uoffset32;
+        }
+        let _ = self.line_starts.splice(first_affected..first_unaffected, new_line_starts);
+        return Ok(());
+    }
+}
+
+/// Replacement of the text covered by `span`
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub struct TextEdit {
+    pub span: Span,
+    pub replacement: String,
+}
+
+impl TextEdit {
+    #[must_use]
+    #[inline(always)]
+    pub const fn new(span: Span, replacement: String) -> Self {
+        return Self { span, replacement };
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn insert(offset: uoffset32, text: String) -> Self {
+        return Self { span: Span { start: offset, end: offset }, replacement: text };
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn delete(span: Span) -> Self {
+        return Self { span, replacement: String::new() };
+    }
+
+    /// Change in length of the code after applying the edit
+    #[must_use]
+    #[inline(always)]
+    pub const fn delta(&self) -> ioffset64 {
+        return self.replacement.len() as ioffset64 - self.span.len() as ioffset64;
+    }
+
+    /// Where the text covered by `span` ends up after applying the edit
+    #[must_use]
+    pub const fn remap(&self, span: Span) -> RemappedSpan {
+        let Span { start, end } = self.span;
+        let replacement_end = start + self.replacement.len() as uoffset32;
+        let delta = self.delta();
+
+        if span.end <= start {
+            return RemappedSpan::Unchanged(span);
+        }
+        if span.start >= end {
+            let shifted_start = (span.start as ioffset64 + delta) as uoffset32;
+            let shifted_end = (span.end as ioffset64 + delta) as uoffset32;
+            return RemappedSpan::Shifted(Span { start: shifted_start, end: shifted_end });
+        }
+        if span.start <= start && end <= span.end {
+            let resized_end = (span.end as ioffset64 + delta) as uoffset32;
+            return RemappedSpan::Resized(Span { start: span.start, end: resized_end });
+        }
+        if start <= span.start && span.end <= end {
+            return RemappedSpan::Invalidated;
+        }
+        if span.start < start {
+            return RemappedSpan::Clamped(Span { start: span.start, end: start });
+        }
+        let clamped_end = (span.end as ioffset64 + delta) as uoffset32;
+        return RemappedSpan::Clamped(Span { start: replacement_end, end: clamped_end });
+    }
+}
+
+/// Result of remapping a [`Span`] across a [`TextEdit`]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum RemappedSpan {
+    /// The span ends before the edit
+    Unchanged(Span),
+
+    /// The span starts after the edit
+    Shifted(Span),
+
+    /// The edit happened entirely inside the span, which now covers the replacement too
+    Resized(Span),
+
+    /// The span partially overlapped the edit, and now only covers its part outside of the edit
+    Clamped(Span),
+
+    /// The span was entirely inside of the replaced text
+    Invalidated,
+}
+
+impl RemappedSpan {
+    #[must_use]
+    #[inline]
+    pub const fn span(self) -> Option<Span> {
+        return match self {
+            Self::Unchanged(span)
+            | Self::Shifted(span)
+            | Self::Resized(span)
+            | Self::Clamped(span) => Some(span),
+            Self::Invalidated => None,
+        };
+    }
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum EditError {
+    OutOfBounds,
+    NotCharBoundary,
+
+    /// The edited code would be too long to be addressed by a [`Span`]
+    TooLarge,
+}
+
+impl core::fmt::Display for EditError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return match self {
+            Self::OutOfBounds => write!(f, "edit span is out of bounds"),
+            Self::NotCharBoundary => write!(f, "edit span is not on character boundaries"),
+            Self::TooLarge => write!(f, "edited code would be too large"),
+        };
+    }
+}
+
+#[expect(clippy::missing_trait_methods, reason = "the default implementations are fine")]
+impl core::error::Error for EditError {}
+
+/// Byte order mark
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum Bom {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Utf32Le,
+    Utf32Be,
+}
+
+impl Bom {
+    pub const UTF8: [u8; 3] = [0xEF, 0xBB, 0xBF];
+    pub const UTF16_LE: [u8; 2] = [0xFF, 0xFE];
+    pub const UTF16_BE: [u8; 2] = [0xFE, 0xFF];
+    pub const UTF32_LE: [u8; 4] = [0xFF, 0xFE, 0x00, 0x00];
+    pub const UTF32_BE: [u8; 4] = [0x00, 0x00, 0xFE, 0xFF];
+
+    #[must_use]
+    #[inline]
+    pub const fn bytes(self) -> &'static [u8] {
+        return match self {
+            Self::Utf8 => &Self::UTF8,
+            Self::Utf16Le => &Self::UTF16_LE,
+            Self::Utf16Be => &Self::UTF16_BE,
+            Self::Utf32Le => &Self::UTF32_LE,
+            Self::Utf32Be => &Self::UTF32_BE,
+        };
+    }
+
+    /// Detects the byte order mark at the start of `bytes`, checking UTF-32 before UTF-16 since
+    /// the little endian UTF-32 mark starts with the little endian UTF-16 mark
+    #[must_use]
+    pub const fn detect(bytes: &[u8]) -> Option<Self> {
+        return match bytes {
+            [0xEF, 0xBB, 0xBF, ..] => Some(Self::Utf8),
+            [0xFF, 0xFE, 0x00, 0x00, ..] => Some(Self::Utf32Le),
+            [0x00, 0x00, 0xFE, 0xFF, ..] => Some(Self::Utf32Be),
+            [0xFF, 0xFE, ..] => Some(Self::Utf16Le),
+            [0xFE, 0xFF, ..] => Some(Self::Utf16Be),
+            _ => None,
+        };
+    }
+}
+
+impl core::fmt::Display for Bom {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return match self {
+            Self::Utf8 => write!(f, "UTF-8"),
+            Self::Utf16Le => write!(f, "UTF-16LE"),
+            Self::Utf16Be => write!(f, "UTF-16BE"),
+            Self::Utf32Le => write!(f, "UTF-32LE"),
+            Self::Utf32Be => write!(f, "UTF-32BE"),
+        };
+    }
+}
+
+/// Invalid UTF-8 sequence found by [`SrcFile::from_bytes`]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct InvalidUtf8 {
+    /// Position of the invalid sequence in the original bytes, including the [`Bom`]
+    pub bytes: Span,
+
+    /// Position of the [`char::REPLACEMENT_CHARACTER`] that replaced the invalid sequence in
+    /// [`SrcFile::code()`]
+    pub span: Span,
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum DecodeError {
+    /// Only UTF-8 encoded code is supported
+    UnsupportedBom(Bom),
+
+    /// The decoded code, of the given length, is too long to be addressed by a [`Span`]
+    TooLarge(uoffset),
+}
+
+impl core::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return match self {
+            Self::UnsupportedBom(bom) => {
+                write!(f, "unsupported {bom} encoding, only UTF-8 is supported")
+            }
+            Self::TooLarge(len) => write!(
+                f,
+                "code is too large ({len} bytes), the maximum is {} bytes",
+                uoffset32::MAX
+            ),
+        };
+    }
+}
+
+#[expect(clippy::missing_trait_methods, reason = "the default implementations are fine")]
+impl core::error::Error for DecodeError {}
+
+// loading
+/// Where to load the code of a [`SrcFile`] from
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum SrcInput<'input> {
+    Path(&'input Path),
+    Stdin,
+    Buffer { name: &'input str, bytes: &'input [u8] },
+}
+
+impl SrcInput<'_> {
+    pub const STDIN_NAME: &'static str = "<stdin>";
+
+    #[must_use]
+    pub fn name(&self) -> String {
+        return match self {
+            Self::Path(path) => path.display().to_string(),
+            Self::Stdin => Self::STDIN_NAME.to_owned(),
+            Self::Buffer { name, .. } => (*name).to_owned(),
+        };
+    }
+}
+
+/// Raw bytes of a loaded [`SrcInput`]
+#[derive(Debug)]
+pub enum SrcBytes<'input> {
+    Borrowed(&'input [u8]),
+    Owned(Vec<u8>),
+    #[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+    Mapped(Mmap),
+}
+
+impl SrcBytes<'_> {
+    #[must_use]
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        return match self {
+            Self::Borrowed(bytes) => bytes,
+            Self::Owned(bytes) => bytes,
+            #[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+            Self::Mapped(mmap) => mmap.as_bytes(),
+        };
+    }
+}
+
+/// Loads [`SrcFile`]s, refusing the ones that are too long to be addressed by a [`Span`]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct SrcLoader {
+    pub(crate) max_len: uoffset32,
+    pub(crate) mmap_threshold: Option<uoffset64>,
+}
+
+impl Default for SrcLoader {
+    #[inline(always)]
+    fn default() -> Self {
+        return Self::DEFAULT;
+    }
+}
+
+impl SrcLoader {
+    pub const DEFAULT: Self = Self { max_len: uoffset32::MAX, mmap_threshold: None };
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn new() -> Self {
+        return Self::DEFAULT;
+    }
+
+    /// Refuse sources longer than `max_len` bytes
+    #[must_use]
+    #[inline(always)]
+    pub const fn with_max_len(mut self, max_len: uoffset32) -> Self {
+        self.max_len = max_len;
+        return self;
+    }
+
+    /// Memory-map files of at least `threshold` bytes read-only instead of reading them into a
+    /// buffer, only available on 64-bit Linux, ignored elsewhere
+    ///
+    /// # Safety
+    ///
+    /// Mapped files must not be modified or truncated, by this or any other process, while the
+    /// [`SrcBytes`] or [`SrcFile`]s loaded from them are alive, see [`Mmap::map`]
+    #[must_use]
+    #[inline(always)]
+    pub const unsafe fn with_mmap(mut self, threshold: uoffset64) -> Self {
+        self.mmap_threshold = Some(threshold);
+        return self;
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn max_len(&self) -> uoffset32 {
+        return self.max_len;
+    }
+
+    /// Reads the raw bytes of `input`, without decoding them
+    pub fn read<'input>(&self, input: SrcInput<'input>) -> Result<SrcBytes<'input>, LoadError> {
+        let max_len = self.max_len as uoffset64;
+        match input {
+            SrcInput::Buffer { bytes, .. } => {
+                if bytes.len() as uoffset64 > max_len {
+                    return Err(LoadError::TooLarge {
+                        len: bytes.len() as uoffset64,
+                        max_len: self.max_len,
+                    });
+                }
+                return Ok(SrcBytes::Borrowed(bytes));
+            }
+            SrcInput::Stdin => {
+                let mut bytes = Vec::new();
+                let stdin = std::io::stdin().lock();
+                let _ = stdin.take(max_len + 1).read_to_end(&mut bytes).map_err(LoadError::Io)?;
+                if bytes.len() as uoffset64 > max_len {
+                    return Err(LoadError::TooLarge {
+                        len: bytes.len() as uoffset64,
+                        max_len: self.max_len,
+                    });
+                }
+                return Ok(SrcBytes::Owned(bytes));
+            }
+            SrcInput::Path(path) => {
+                let file = File::open(path).map_err(LoadError::Io)?;
+                let len = file.metadata().map_err(LoadError::Io)?.len();
+                if len > max_len {
+                    return Err(LoadError::TooLarge { len, max_len: self.max_len });
+                }
+
+                #[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+                if let Some(threshold) = self.mmap_threshold
+                    && len > 0
+                    && len >= threshold
+                {
+                    // SAFETY: `len` was just read from the file, which is guaranteed not to be
+                    // modified by the caller of `with_mmap`
+                    let mmap =
+                        unsafe { Mmap::map(&file, len as uoffset) }.map_err(LoadError::Io)?;
+                    return Ok(SrcBytes::Mapped(mmap));
+                }
+
+                // the file could grow in the meantime
+                let mut bytes = Vec::with_capacity(len as uoffset);
+                let _ = file.take(max_len + 1).read_to_end(&mut bytes).map_err(LoadError::Io)?;
+                if bytes.len() as uoffset64 > max_len {
+                    return Err(LoadError::TooLarge {
+                        len: bytes.len() as uoffset64,
+                        max_len: self.max_len,
+                    });
+                }
+                return Ok(SrcBytes::Owned(bytes));
+            }
+        }
+    }
+
+    /// Reads and decodes `input`, see [`SrcFile::from_bytes`]
+    pub fn load(&self, input: SrcInput<'_>) -> Result<(SrcFile, Vec<InvalidUtf8>), LoadError> {
+        let name = input.name();
+        let decoded = match self.read(input)? {
+            SrcBytes::Borrowed(bytes) => SrcFile::from_bytes(name, bytes),
+            SrcBytes::Owned(bytes) => SrcFile::from_bytes(name, &bytes),
+            #[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+            SrcBytes::Mapped(mmap) => SrcFile::from_mmap(name, mmap),
+        };
+        return decoded.map_err(LoadError::Decode);
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(std::io::Error),
+
+    /// The source, of at least the given length, is longer than the maximum allowed length
+    TooLarge {
+        len: uoffset64,
+        max_len: uoffset32,
+    },
+
+    Decode(DecodeError),
+}
+
+impl core::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return match self {
+            Self::Io(err) => write!(f, "could not read source: {err}"),
+            Self::TooLarge { len, max_len } => {
+                write!(f, "source is too large ({len} bytes), the maximum is {max_len} bytes")
+            }
+            Self::Decode(err) => write!(f, "{err}"),
+        };
+    }
+}
+
+#[expect(clippy::missing_trait_methods, reason = "the default implementations are fine")]
+impl core::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        return match self {
+            Self::Io(err) => Some(err),
+            Self::TooLarge { .. } => None,
+            Self::Decode(err) => Some(err),
+        };
+    }
+}
+
+#[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+mod linux {
+    use core::ffi::{c_int, c_void};
+
+    pub(super) const PROT_READ: c_int = 0x1;
+    pub(super) const MAP_PRIVATE: c_int = 0x2;
+    pub(super) const MAP_FAILED: *mut c_void = !0 as *mut c_void;
+
+    unsafe extern "C" {
+        pub(super) fn mmap(
+            addr: *mut c_void,
+            len: usize,
+            prot: c_int,
+            flags: c_int,
+            fd: c_int,
+            offset: i64,
+        ) -> *mut c_void;
+
+        pub(super) fn munmap(addr: *mut c_void, len: usize) -> c_int;
+    }
+}
+
+/// Read-only memory mapping of a whole file
+#[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+#[derive(Debug)]
+pub struct Mmap {
+    ptr: core::ptr::NonNull<u8>,
+    len: uoffset,
+}
+
+#[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+impl Mmap {
+    /// Maps the first `len` bytes of `file`, failing if `len` is zero
+    ///
+    /// # Safety
+    ///
+    /// `len` must not be greater than the length of the file, and the file must not be modified or
+    /// truncated, by this or any other process, while it's mapped, since the mapped bytes would
+    /// change under shared references or fault on access
+    pub unsafe fn map(file: &File, len: uoffset) -> std::io::Result<Self> {
+        let fd = file.as_raw_fd();
+        let addr = unsafe {
+            linux::mmap(core::ptr::null_mut(), len, linux::PROT_READ, linux::MAP_PRIVATE, fd, 0)
+        };
+        if addr == linux::MAP_FAILED {
+            return Err(std::io::Error::last_os_error());
+        }
+        let Some(ptr) = core::ptr::NonNull::new(addr.cast::<u8>()) else {
+            return Err(std::io::Error::last_os_error());
+        };
+        return Ok(Self { ptr, len });
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn as_bytes(&self) -> &[u8] {
+        return unsafe { core::slice::from_raw_parts(self.ptr.as_ptr(), self.len) };
+    }
+}
+
+#[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        let _ = unsafe { linux::munmap(self.ptr.as_ptr().cast(), self.len) };
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FileId(pub(crate) uoffset32);
+
+impl FileId {
+    #[must_use]
+    #[inline(always)]
+    pub const fn index(self) -> uoffset32 {
+        return self.0;
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FileSpan {
+    pub file: FileId,
+    pub span: Span,
+}
+
+impl FileSpan {
+    #[must_use]
+    #[inline(always)]
+    pub const fn new(file: FileId, span: Span) -> Self {
+        return Self { file, span };
+    }
+}
+
+/// Resolved location of a [`FileSpan`]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct FileLocation<'src> {
+    pub name: &'src str,
+    pub start: LineColumn,
+    pub end: LineColumn,
+}
+
+impl core::fmt::Display for FileLocation<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return write!(f, "{}:{}", self.name, self.start);
+    }
+}
+
+/// Collection of all the files involved in a compilation
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub struct SrcMap {
+    pub(crate) files: Vec<SrcFile>,
+}
+
+impl SrcMap {
+    #[must_use]
+    #[inline(always)]
+    pub const fn new() -> Self {
+        return Self { files: Vec::new() };
+    }
+
+    pub fn add(&mut self, file: SrcFile) -> FileId {
+        let id = FileId(self.files.len() as uoffset32);
+        self.files.push(file);
+        return id;
+    }
+
+    /// Returns [`None`] if `code` is too long to be addressed by a [`Span`]
+    pub fn add_code(&mut self, name: String, code: String) -> Option<FileId> {
+        let file = SrcFile::new(name, code)?;
+        return Some(self.add(file));
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub fn files(&self) -> &[SrcFile] {
+        return &self.files;
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub fn file(&self, id: FileId) -> Option<&SrcFile> {
+        return self.files.get(id.0 as usize);
+    }
+
+    #[must_use]
+    pub fn name(&self, id: FileId) -> Option<&str> {
+        let file = self.file(id)?;
+        return Some(file.name());
+    }
+
+    #[must_use]
+    pub fn text(&self, span: FileSpan) -> Option<&str> {
+        let file = self.file(span.file)?;
+        return span.span.slice(file.code());
+    }
+
+    #[must_use]
+    pub fn locate(&self, span: FileSpan) -> Option<FileLocation<'_>> {
+        let file = self.file(span.file)?;
+        let (start, end) = file.span_line_columns(span.span)?;
+        return Some(FileLocation { name: file.name(), start, end });
+    }
+}
+
+// expansions
+/// Expansion some code was produced by, [`ExpnId::ROOT`] being the code written by the user
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ExpnId(pub(crate) uoffset32);
+
+impl ExpnId {
+    pub const ROOT: Self = Self(0);
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn index(self) -> uoffset32 {
+        return self.0;
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn is_root(self) -> bool {
+        return self.0 == Self::ROOT.0;
+    }
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub enum ExpnKind {
+    Macro {
+        name: String,
+    },
+
+    /// Compiler-generated code, e.g.: the desugaring of a `for` loop
+    Desugaring {
+        name: String,
+    },
+}
+
+impl core::fmt::Display for ExpnKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return match self {
+            Self::Macro { name } => write!(f, "macro `{name}`"),
+            Self::Desugaring { name } => write!(f, "desugaring of {name}"),
+        };
+    }
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct ExpnData {
+    pub kind: ExpnKind,
+
+    /// Where the expansion was invoked, which can itself be inside of another expansion
+    pub call_site: ExpnSpan,
+}
+
+/// [`FileSpan`] marked with the expansion it was produced by
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub struct ExpnSpan {
+    pub span: FileSpan,
+    pub expn: ExpnId,
+}
+
+impl ExpnSpan {
+    #[must_use]
+    #[inline(always)]
+    pub const fn new(span: FileSpan, expn: ExpnId) -> Self {
+        return Self { span, expn };
+    }
+
+    /// Span of code written by the user
+    #[must_use]
+    #[inline(always)]
+    pub const fn root(span: FileSpan) -> Self {
+        return Self { span, expn: ExpnId::ROOT };
+    }
+}
+
+/// Collection of all the expansions performed during a compilation
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub struct Expansions {
+    /// Data of the expansion with id `n` is at index `n - 1`, since [`ExpnId::ROOT`] has none
+    pub(crate) expansions: Vec<ExpnData>,
+}
+
+impl Expansions {
+    #[must_use]
+    #[inline(always)]
+    pub const fn new() -> Self {
+        return Self { expansions: Vec::new() };
+    }
+
+    /// # Panics
+    ///
+    /// If the call site refers to an expansion not in this collection
+    pub fn add(&mut self, kind: ExpnKind, call_site: ExpnSpan) -> ExpnId {
+        assert!(
+            call_site.expn.0 as usize <= self.expansions.len(),
+            "call site refers to an unknown expansion"
+        );
+        self.expansions.push(ExpnData { kind, call_site });
+        return ExpnId(self.expansions.len() as uoffset32);
+    }
+
+    /// Returns [`None`] for [`ExpnId::ROOT`] and unknown expansions
+    #[must_use]
+    pub fn data(&self, id: ExpnId) -> Option<&ExpnData> {
+        let index = id.0.checked_sub(1)?;
+        return self.expansions.get(index as usize);
+    }
+
+    /// Expansions `span` comes from, from the innermost to the outermost
+    #[must_use]
+    #[inline(always)]
+    pub const fn backtrace(&self, span: ExpnSpan) -> Backtrace<'_> {
+        return Backtrace { expansions: self, current: span.expn };
+    }
+
+    /// Span in the code written by the user that `span` was ultimately expanded from
+    #[must_use]
+    pub fn user_span(&self, span: ExpnSpan) -> FileSpan {
+        return match self.backtrace(span).last() {
+            Some(outermost) => outermost.call_site.span,
+            None => span.span,
+        };
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Backtrace<'expansions> {
+    expansions: &'expansions Expansions,
+    current: ExpnId,
+}
+
+#[expect(clippy::missing_trait_methods, reason = "the default implementations are fine")]
+impl<'expansions> Iterator for Backtrace<'expansions> {
+    type Item = &'expansions ExpnData;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let data = self.expansions.data(self.current)?;
+        self.current = data.call_site.expn;
+        return Some(data);
+    }
+}
+
+/// A value together with the [`Span`] it was found at
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub struct Spanned<T, I: SpanIndex = uoffset32> {
+    pub value: T,
+    pub span: Span<I>,
+}
+
+impl<T, I: SpanIndex> Spanned<T, I> {
+    #[must_use]
+    #[inline(always)]
+    pub const fn new(value: T, span: Span<I>) -> Self {
+        return Self { value, span };
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn span(&self) -> Span<I> {
+        return self.span;
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn value(&self) -> &T {
+        return &self.value;
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub fn into_value(self) -> T {
+        return self.value;
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn as_ref(&self) -> Spanned<&T, I> {
+        return Spanned { value: &self.value, span: self.span };
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn as_mut(&mut self) -> Spanned<&mut T, I> {
+        return Spanned { value: &mut self.value, span: self.span };
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Spanned<U, I> {
+        return Spanned { value: f(self.value), span: self.span };
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub fn eq_ignore_span<U, J: SpanIndex>(&self, other: &Spanned<U, J>) -> bool
+    where
+        T: PartialEq<U>,
+    {
+        return self.value == other.value;
+    }
+}
+
+impl<T> Spanned<T> {
+    /// Displays the value followed by the line and column of the start of its span, like
+    /// `value @ 3:14`
+    #[must_use]
+    #[inline(always)]
+    pub const fn display<'src>(&'src self, src: &'src SrcFile) -> SpannedDisplay<'src, T> {
+        return SpannedDisplay { spanned: self, src };
+    }
+}
+
+/// Displays the value followed by its span, like `value @ 5..12`
+impl<T: core::fmt::Display, I: SpanIndex> core::fmt::Display for Spanned<T, I> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return write!(f, "{} @ {}..{}", self.value, self.span.start, self.span.end);
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SpannedDisplay<'src, T> {
+    spanned: &'src Spanned<T>,
+    src: &'src SrcFile,
+}
+
+impl<T: core::fmt::Display> core::fmt::Display for SpannedDisplay<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return match self.src.line_column(self.spanned.span.start) {
+            Some(line_column) => write!(f, "{} @ {line_column}", self.spanned.value),
+            None => write!(f, "{}", self.spanned),
+        };
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum ColumnKind {
+    /// UTF-8 code units
+    #[default]
+    Byte,
+
+    /// Unicode scalar values
+    Char,
+
+    /// UTF-16 code units, as used by most editors
+    Utf16,
+
+    /// Terminal cells, with tabs expanded to the next multiple of `tab_width` and wide characters
+    /// taking two cells
+    Display { tab_width: uoffset8 },
+}
+
+impl ColumnKind {
+    pub const DEFAULT_TAB_WIDTH: uoffset8 = 4;
+    pub const DISPLAY: Self = Self::Display { tab_width: Self::DEFAULT_TAB_WIDTH };
+
+    /// Column reached after `character` when starting from the 0-based `column`
+    #[must_use]
+    #[inline]
+    pub const fn advance(self, column: uoffset32, character: utf32) -> uoffset32 {
+        return match self {
+            Self::Byte => column + character.len_utf8() as uoffset32,
+            Self::Char => column + 1,
+            Self::Utf16 => column + character.len_utf16() as uoffset32,
+            Self::Display { tab_width } => {
+                if character != '\t' {
+                    column + display_width(character)
+                } else if tab_width == 0 {
+                    column
+                } else {
+                    let width = tab_width as uoffset32;
+                    column + width - column % width
+                }
+            }
+        };
+    }
+}
+
+/// Number of terminal cells taken by `character`, which is `0` for control, combining and
+/// zero-width characters, `2` for East Asian wide and fullwidth characters and `1` otherwise.
+///
+/// Tabs are not expanded, see [`ColumnKind::Display`].
+#[rustfmt::skip]
+#[must_use]
+pub const fn display_width(character: utf32) -> uoffset32 {
+    return match character as u32 {
+        // control characters
+        0x0000..=0x001F | 0x007F..=0x009F => 0,
+
+        // combining marks, zero-width characters and variation selectors
+        0x0300..=0x036F | 0x0483..=0x0489 | 0x0591..=0x05BD | 0x0610..=0x061A | 0x064B..=0x065F |
+        0x1AB0..=0x1AFF | 0x1DC0..=0x1DFF | 0x200B..=0x200F | 0x202A..=0x202E | 0x2060..=0x2064 |
+        0x20D0..=0x20FF | 0xFE00..=0xFE0F | 0xFE20..=0xFE2F | 0xFEFF | 0xE0100..=0xE01EF => 0,
+
+        // East Asian wide and fullwidth characters
+        0x1100..=0x115F | 0x231A..=0x231B | 0x2329..=0x232A | 0x23E9..=0x23EC | 0x23F0 | 0x23F3 |
+        0x25FD..=0x25FE | 0x2614..=0x2615 | 0x2648..=0x2653 | 0x267F | 0x2693 | 0x26A1 |
+        0x26AA..=0x26AB | 0x26BD..=0x26BE | 0x26C4..=0x26C5 | 0x26CE | 0x26D4 | 0x26EA |
+        0x26F2..=0x26F3 | 0x26F5 | 0x26FA | 0x26FD | 0x2705 | 0x270A..=0x270B | 0x2728 | 0x274C |
+        0x274E | 0x2753..=0x2755 | 0x2757 | 0x2795..=0x2797 | 0x27B0 | 0x27BF | 0x2B1B..=0x2B1C |
+        0x2B50 | 0x2B55 | 0x2E80..=0x303E | 0x3041..=0x33FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF |
+        0xA000..=0xA4CF | 0xA960..=0xA97F | 0xAC00..=0xD7A3 | 0xF900..=0xFAFF | 0xFE10..=0xFE19 |
+        0xFE30..=0xFE6F | 0xFF00..=0xFF60 | 0xFFE0..=0xFFE6 | 0x16FE0..=0x16FE4 |
+        0x17000..=0x18CFF | 0x1B000..=0x1B2FF | 0x1F004 | 0x1F0CF | 0x1F18E | 0x1F191..=0x1F19A |
+        0x1F200..=0x1F251 | 0x1F300..=0x1F320 | 0x1F32D..=0x1F335 | 0x1F337..=0x1F37C |
+        0x1F37E..=0x1F393 | 0x1F3A0..=0x1F3CA | 0x1F3CF..=0x1F3D3 | 0x1F3E0..=0x1F3F0 | 0x1F3F4 |
+        0x1F3F8..=0x1F43E | 0x1F440 | 0x1F442..=0x1F4FC | 0x1F4FF..=0x1F53D | 0x1F54B..=0x1F54E |
+        0x1F550..=0x1F567 | 0x1F57A | 0x1F595..=0x1F596 | 0x1F5A4 | 0x1F5FB..=0x1F64F |
+        0x1F680..=0x1F6C5 | 0x1F6CC | 0x1F6D0..=0x1F6D2 | 0x1F6D5..=0x1F6D7 | 0x1F6DC..=0x1F6DF |
+        0x1F6EB..=0x1F6EC | 0x1F6F4..=0x1F6FC | 0x1F7E0..=0x1F7EB | 0x1F7F0 | 0x1F90C..=0x1F93A |
+        0x1F93C..=0x1F945 | 0x1F947..=0x1F9FF | 0x1FA70..=0x1FAFF | 0x20000..=0x2FFFD |
+        0x30000..=0x3FFFD => 2,
+
+        _ => 1,
+    };
+}
+
+/// Converts the 0-based `byte_column` into a 0-based column of `kind`, where `line` is the text
+/// starting at the beginning of a line
+///
+/// Returns [`None`] if `byte_column` is past the end of `line` or not on a character boundary.
+#[must_use]
+pub fn column_of(line: &str, byte_column: uoffset32, kind: ColumnKind) -> Option<uoffset32> {
+    let prefix = line.get(..byte_column as usize)?;
+    if let ColumnKind::Byte = kind {
+        return Some(byte_column);
+    }
+
+    let mut column = 0;
+    for character in prefix.chars() {
+        column = kind.advance(column, character);
+    }
+    return Some(column);
+}
+
+/// Converts the 0-based `column` of `kind` into a 0-based byte column, where `line` is the text of
+/// a line, excluding its line terminator
+///
+/// Returns [`None`] if `column` is past the end of `line` or falls inside a character, like in the
+/// middle of a surrogate pair, a wide character or an expanded tab.
+#[must_use]
+pub fn byte_column_of(line: &str, column: uoffset32, kind: ColumnKind) -> Option<uoffset32> {
+    if let ColumnKind::Byte = kind {
+        if !line.is_char_boundary(column as usize) {
+            return None;
+        }
+        return Some(column);
+    }
+
+    let mut current_column = 0;
+    for (byte_column, character) in line.char_indices() {
+        if current_column == column {
+            return Some(byte_column as uoffset32);
+        }
+        if current_column > column {
+            return None;
+        }
+        current_column = kind.advance(current_column, character);
+    }
+
+    if current_column == column {
+        return Some(line.len() as uoffset32);
+    }
+    return None;
+}
+
+// scanning
+/// Byte-oriented scanner over some code, tracking the current offset
+///
+/// Byte operations can stop in the middle of a multi-byte character, the `*_char` operations
+/// always move by whole characters
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct Cursor<'code> {
+    pub(crate) code: &'code str,
+    pub(crate) offset: uoffset32,
+}
+
+/// Checkpoint of a [`Cursor`], to rewind to or to get the [`Span`] consumed since
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Mark(pub(crate) uoffset32);
+
+impl Mark {
+    #[must_use]
+    #[inline(always)]
+    pub const fn offset(self) -> uoffset32 {
+        return self.0;
+    }
+}
+
+impl<'code> Cursor<'code> {
+    /// Returns [`None`] if `code` is too long to be addressed by a [`Span`]
+    #[must_use]
+    #[inline]
+    pub const fn new(code: &'code str) -> Option<Self> {
+        if code.len() > uoffset32::MAX as usize {
+            return None;
+        }
+        return Some(Self { code, offset: 0 });
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn of(src: &'code SrcFile) -> Self {
+        return Self { code: src.code(), offset: 0 };
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn code(&self) -> &'code str {
+        return self.code;
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn offset(&self) -> uoffset32 {
+        return self.offset;
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn is_at_end(&self) -> bool {
+        return self.offset as usize >= self.code.len();
+    }
+
+    /// Bytes left to scan
+    #[must_use]
+    #[inline]
+    pub const fn remaining(&self) -> &'code [ascii] {
+        let bytes = self.code.as_bytes();
+        let (_, rest) = bytes.split_at(self.offset as usize);
+        return rest;
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn peek(&self) -> Option<ascii> {
+        return self.peek_nth(0);
+    }
+
+    /// Peeks the byte `n` bytes after the current one
+    #[must_use]
+    #[inline]
+    pub const fn peek_nth(&self, n: uoffset32) -> Option<ascii> {
+        let index = self.offset as usize + n as usize;
+        if index >= self.code.len() {
+            return None;
+        }
+        return Some(self.code.as_bytes()[index]);
+    }
+
+    #[inline]
+    pub const fn bump(&mut self) -> Option<ascii> {
+        let Some(byte) = self.peek() else {
+            return None;
+        };
+        self.offset += 1;
+        return Some(byte);
+    }
+
+    /// Returns [`None`] at the end of the code or if not at a character boundary
+    #[must_use]
+    #[inline]
+    pub fn peek_char(&self) -> Option<utf32> {
+        return self.code.get(self.offset as usize..)?.chars().next();
+    }
+
+    /// Returns [`None`] at the end of the code or if not at a character boundary
+    #[inline]
+    pub fn bump_char(&mut self) -> Option<utf32> {
+        let character = self.peek_char()?;
+        self.offset += character.len_utf8() as uoffset32;
+        return Some(character);
+    }
+
+    /// Consumes `expected` if it's the next byte
+    #[inline]
+    pub const fn eat(&mut self, expected: ascii) -> bool {
+        return match self.peek() {
+            Some(byte) if byte == expected => {
+                self.offset += 1;
+                true
+            }
+            Some(_) | None => false,
+        };
+    }
+
+    /// Consumes the next byte if it satisfies `predicate`
+    #[inline]
+    pub fn eat_if<P: FnOnce(ascii) -> bool>(&mut self, predicate: P) -> Option<ascii> {
+        let byte = self.peek()?;
+        if !predicate(byte) {
+            return None;
+        }
+        self.offset += 1;
+        return Some(byte);
+    }
+
+    /// Consumes bytes while they satisfy `predicate`, returning the [`Span`] of the consumed bytes
+    #[inline]
+    pub fn eat_while<P: FnMut(ascii) -> bool>(&mut self, mut predicate: P) -> Span {
+        let start = self.offset;
+        while let Some(byte) = self.peek() {
+            if !predicate(byte) {
+                break;
+            }
+            self.offset += 1;
+        }
+        return Span { start, end: self.offset };
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn mark(&self) -> Mark {
+        return Mark(self.offset);
+    }
+
+    /// Marks past the end of the code, i.e. taken from a longer cursor, rewind to the end
+    #[inline]
+    pub const fn rewind(&mut self, mark: Mark) {
+        let len = self.code.len() as uoffset32;
+        self.offset = if mark.0 > len { len } else { mark.0 };
+    }
+
+    /// [`Span`] of what was consumed since `mark`, empty if the cursor was rewound before it
+    #[must_use]
+    #[inline]
+    pub const fn span_since(&self, mark: Mark) -> Span {
+        if self.offset < mark.0 {
+            return Span { start: self.offset, end: self.offset };
+        }
+        return Span { start: mark.0, end: self.offset };
+    }
+}
+
+// indentation
+/// Character used to indent the lines of some code
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum IndentStyle {
+    Spaces,
+    Tabs,
+}
+
+impl IndentStyle {
+    #[must_use]
+    #[inline]
+    pub const fn ascii(self) -> ascii {
+        return match self {
+            Self::Spaces => b' ',
+            Self::Tabs => b'\t',
+        };
+    }
+}
+
+impl core::fmt::Display for IndentStyle {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return match self {
+            Self::Spaces => write!(f, "spaces"),
+            Self::Tabs => write!(f, "tabs"),
+        };
+    }
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum IndentEvent {
+    /// Span of the indentation added to the previous level
+    Indent(Span),
+
+    /// Empty span at the start of the first token of the dedented line, or at the end of the code
+    Dedent(Span),
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum IndentError {
+    /// The indentation of a line uses a different character than the first indented line
+    MixedTabsAndSpaces { span: Span, expected: IndentStyle },
+
+    /// The line is dedented to a level that doesn't match any enclosing level
+    UnknownDedent { span: Span },
+}
+
+impl core::fmt::Display for IndentError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return match self {
+            Self::MixedTabsAndSpaces { expected, .. } => {
+                write!(f, "inconsistent use of tabs and spaces, expected only {expected}")
+            }
+            Self::UnknownDedent { .. } => {
+                write!(f, "dedent does not match any outer indentation level")
+            }
+        };
+    }
+}
+
+#[expect(clippy::missing_trait_methods, reason = "the default implementations are fine")]
+impl core::error::Error for IndentError {}
+
+/// Indentation changes of some code, ignoring blank lines
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub struct Indentation {
+    pub events: Vec<IndentEvent>,
+    pub errors: Vec<IndentError>,
+
+    /// Style of the first indented line, [`None`] if no line is indented
+    pub style: Option<IndentStyle>,
+}
+
+impl Indentation {
+    /// Closes all the open indentation levels at the end of the code
+    #[must_use]
+    pub fn of(code: &str) -> Self {
+        let bytes = code.as_bytes();
+        let mut indentation = Self::default();
+        let mut levels: Vec<uoffset32> = vec![0];
+
+        for (line, _) in lines(code) {
+            let mut indentation_end = line.start;
+            while indentation_end < line.end
+                && matches!(bytes[indentation_end as usize], b' ' | b'\t')
+            {
+                indentation_end += 1;
+            }
+            if indentation_end == line.end {
+                continue;
+            }
+
+            let indentation_span = Span { start: line.start, end: indentation_end };
+            let width = indentation_span.len();
+            if width > 0 {
+                let first = bytes[line.start as usize];
+                let style = match indentation.style {
+                    Some(style) => style,
+                    None if first == IndentStyle::Tabs.ascii() => IndentStyle::Tabs,
+                    None => IndentStyle::Spaces,
+                };
+                indentation.style = Some(style);
+
+                let whitespace = &bytes[line.start as usize..indentation_end as usize];
+                if whitespace.iter().any(|&byte| return byte != style.ascii()) {
+                    indentation.errors.push(IndentError::MixedTabsAndSpaces {
+                        span: indentation_span,
+                        expected: style,
+                    });
+                }
+            }
+
+            let mut current = levels.last().copied().unwrap_or_default();
+            if width > current {
+                let indent = Span { start: line.start + current, end: indentation_end };
+                indentation.events.push(IndentEvent::Indent(indent));
+                levels.push(width);
+                continue;
+            }
+
+            let dedent = Span { start: indentation_end, end: indentation_end };
+            while width < current {
+                let _ = levels.pop();
+                indentation.events.push(IndentEvent::Dedent(dedent));
+                current = levels.last().copied().unwrap_or_default();
+            }
+            if width != current {
+                indentation.errors.push(IndentError::UnknownDedent { span: indentation_span });
+            }
+        }
+
+        let len = code.len() as uoffset32;
+        for _ in 1..levels.len() {
+            indentation.events.push(IndentEvent::Dedent(Span { start: len, end: len }));
+        }
+        return indentation;
+    }
+}
+
+// suspicious characters
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum SuspiciousKind {
+    /// Changes the visible direction of the text, making it look different than how it's parsed
+    BidiControl,
+
+    /// Invisible character
+    ZeroWidth,
+
+    /// Looks like the given ascii character
+    Confusable(ascii),
+}
+
+/// Character that could make the code look different than how it's parsed
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct SuspiciousChar {
+    pub span: Span,
+    pub character: utf32,
+    pub kind: SuspiciousKind,
+}
+
+impl core::fmt::Display for SuspiciousChar {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let codepoint = self.character as u32;
+        return match self.kind {
+            SuspiciousKind::BidiControl => {
+                write!(
+                    f,
+                    "unicode codepoint U+{codepoint:04X} changes the visible direction of text"
+                )
+            }
+            SuspiciousKind::ZeroWidth => write!(f, "invisible unicode codepoint U+{codepoint:04X}"),
+            SuspiciousKind::Confusable(ascii) => write!(
+                f,
+                "unicode codepoint U+{codepoint:04X} looks like '{}'",
+                ascii.escape_ascii()
+            ),
+        };
+    }
+}
+
+/// Kind of suspicious character `character` is, if any
+#[must_use]
+pub const fn suspicious_kind(character: utf32) -> Option<SuspiciousKind> {
+    return match character {
+        '\u{202A}'..='\u{202E}'
+        | '\u{2066}'..='\u{2069}'
+        | '\u{200E}'
+        | '\u{200F}'
+        | '\u{061C}' => Some(SuspiciousKind::BidiControl),
+        '\u{200B}'..='\u{200D}' | '\u{2060}' | '\u{FEFF}' | '\u{180E}' | '\u{00AD}' => {
+            Some(SuspiciousKind::ZeroWidth)
+        }
+        _ => match confusable_ascii(character) {
+            Some(ascii) => Some(SuspiciousKind::Confusable(ascii)),
+            None => None,
+        },
+    };
+}
+
+/// Ascii character `character` can be mistaken for
+#[must_use]
+pub const fn confusable_ascii(character: utf32) -> Option<ascii> {
+    let ascii = match character {
+        // fullwidth forms
+        '\u{FF01}'..='\u{FF5E}' => return Some((character as u32 - 0xFEE0) as ascii),
+
+        // cyrillic
+        '\u{0430}' => b'a',
+        '\u{0435}' => b'e',
+        '\u{043E}' => b'o',
+        '\u{0440}' => b'p',
+        '\u{0441}' => b'c',
+        '\u{0443}' => b'y',
+        '\u{0445}' => b'x',
+        '\u{0455}' => b's',
+        '\u{0456}' => b'i',
+        '\u{0458}' => b'j',
+        '\u{04BB}' => b'h',
+        '\u{0501}' => b'd',
+        '\u{0405}' => b'S',
+        '\u{0406}' => b'I',
+        '\u{0408}' => b'J',
+        '\u{0410}' => b'A',
+        '\u{0412}' => b'B',
+        '\u{0415}' => b'E',
+        '\u{041A}' => b'K',
+        '\u{041C}' => b'M',
+        '\u{041D}' => b'H',
+        '\u{041E}' => b'O',
+        '\u{0420}' => b'P',
+        '\u{0421}' => b'C',
+        '\u{0422}' => b'T',
+        '\u{0425}' => b'X',
+
+        // greek
+        '\u{0391}' => b'A',
+        '\u{0392}' => b'B',
+        '\u{0395}' => b'E',
+        '\u{0396}' => b'Z',
+        '\u{0397}' => b'H',
+        '\u{0399}' => b'I',
+        '\u{039A}' => b'K',
+        '\u{039C}' => b'M',
+        '\u{039D}' => b'N',
+        '\u{039F}' => b'O',
+        '\u{03A1}' => b'P',
+        '\u{03A4}' => b'T',
+        '\u{03A5}' => b'Y',
+        '\u{03A7}' => b'X',
+        '\u{03B1}' => b'a',
+        '\u{03BD}' => b'v',
+        '\u{03BF}' => b'o',
+        '\u{037E}' => b';',
+
+        // punctuation
+        '\u{00A0}' | '\u{2000}'..='\u{200A}' | '\u{202F}' | '\u{205F}' | '\u{3000}' => b' ',
+        '\u{2010}'..='\u{2013}' | '\u{2212}' => b'-',
+        '\u{2018}' | '\u{2019}' | '\u{201B}' | '\u{2032}' => b'\'',
+        '\u{201C}' | '\u{201D}' | '\u{201F}' | '\u{2033}' => b'"',
+        '\u{2044}' | '\u{2215}' => b'/',
+        '\u{2216}' => b'\\',
+        '\u{01C3}' => b'!',
+        '\u{02D0}' | '\u{0589}' | '\u{2236}' => b':',
+        '\u{201A}' => b',',
+        '\u{2024}' => b'.',
+        _ => return None,
+    };
+    return Some(ascii);
+}
+
+/// Bidirectional control characters, zero-width characters and characters that look like ascii
+/// characters found in `code`
+#[must_use]
+pub fn suspicious_chars(code: &str) -> Vec<SuspiciousChar> {
+    let mut found = Vec::new();
+    for (offset, character) in code.char_indices() {
+        if character.is_ascii() {
+            continue;
+        }
+        let Some(kind) = suspicious_kind(character) else {
+            continue;
+        };
+        let start = offset as uoffset32;
+        let end = start + character.len_utf8() as uoffset32;
+        found.push(SuspiciousChar { span: Span { start, end }, character, kind });
+    }
+    return found;
+}
+
+#[expect(clippy::should_panic_without_expect, clippy::unwrap_used, unused_imports)]
+#[cfg(test)]
+#[rustfmt::skip]
+mod tests {
+    mod _0_1_0_functionality {
+        use crate::{src_code::Span, test_assert};
+
+        const _: () = test_assert!(unsafe { Span::new_unchecked(0, 0) }, == Span { start: 0, end: 0 });
+
+        #[should_panic]
+        #[test]
+        const fn span_new_unchecked_should_panic() {
+            let _ = unsafe { Span::new_unchecked(1, 0) };
+        }
+
+        const _: () = test_assert!(Span::new(0, 0), == Some(Span { start: 0, end: 0 }));
+        const _: () = test_assert!(Span::new(1, 0), == None);
+    }
+
+    mod _0_1_2_span_algebra {
+        use crate::{src_code::Span, test_assert};
+
+        const A: Span = Span { start: 2, end: 5 };
+        const B: Span = Span { start: 4, end: 8 };
+        const C: Span = Span { start: 5, end: 6 };
+        const D: Span = Span { start: 7, end: 9 };
+
+        const _: () = test_assert!(A.len(), == 3);
+        const _: () = test_assert!(A.is_empty(), == false);
+        const _: () = test_assert!(Span::empty(3).is_empty(), == true);
+
+        const _: () = test_assert!(A.join(B), == Span { start: 2, end: 8 });
+        const _: () = test_assert!(A.join(D), == Span { start: 2, end: 9 });
+        const _: () = test_assert!(A.intersect(B), == Some(Span { start: 4, end: 5 }));
+        const _: () = test_assert!(A.intersect(C), == Some(Span { start: 5, end: 5 }));
+        const _: () = test_assert!(A.intersect(D), == None);
+
+        const _: () = test_assert!(A.contains(2), == true);
+        const _: () = test_assert!(A.contains(5), == false);
+        const _: () = test_assert!(B.contains_span(C), == true);
+        const _: () = test_assert!(B.contains_span(D), == false);
+        const _: () = test_assert!(A.overlaps(B), == true);
+        const _: () = test_assert!(A.overlaps(C), == false);
+        const _: () = test_assert!(A.is_before(C), == true);
+        const _: () = test_assert!(C.is_after(A), == true);
+        const _: () = test_assert!(B.is_before(C), == false);
+
+        const _: () = test_assert!(B.shrink(1, 2), == Some(Span { start: 5, end: 6 }));
+        const _: () = test_assert!(B.shrink(3, 2), == None);
+        const _: () = test_assert!(B.grow(4, 1), == Some(Span { start: 0, end: 9 }));
+        const _: () = test_assert!(B.grow(5, 0), == None);
+        const _: () = test_assert!(Span { start: 0, end: u32::MAX }.grow(0, 1), == None);
+        const _: () = test_assert!(B.shift_by(-4), == Some(Span { start: 0, end: 4 }));
+        const _: () = test_assert!(B.shift_by(-5), == None);
+        const _: () = test_assert!(B.shift_by(2), == Some(Span { start: 6, end: 10 }));
+        const _: () = test_assert!(Span { start: 0, end: u32::MAX }.shift_by(1), == None);
+
+        const _: () = test_assert!(Span { start: 1, end: 3 }.slice("abcd"), == Some(text) if text.len() == 2);
+        const _: () = test_assert!(Span { start: 1, end: 5 }.slice("abcd"), == None);
+        const _: () = test_assert!(Span { start: 0, end: 1 }.slice("\u{e9}"), == None);
+
+        const _: () = test_assert!(A.with_start(5), == Some(Span { start: 5, end: 5 }));
+        const _: () = test_assert!(A.with_start(6), == None);
+        const _: () = test_assert!(A.with_end(1), == None);
+
+        #[test]
+        fn ordering_and_setters() {
+            assert!(A < B && B < C && C < D);
+            assert_eq!(Span { start: 1, end: 3 }.slice("abcd"), Some("bc"));
+
+            let mut span = A;
+            span.set_end(10);
+            span.set_start(10);
+            assert_eq!(span, Span { start: 10, end: 10 });
+            unsafe { span.set_start_unchecked(0); }
+            unsafe { span.set_end_unchecked(1); }
+            assert_eq!(span, Span { start: 0, end: 1 });
+        }
+
+        #[should_panic]
+        #[test]
+        fn span_set_start_should_panic() {
+            let mut span = A;
+            span.set_start(6);
+        }
+
+        #[should_panic]
+        #[test]
+        fn span_set_end_should_panic() {
+            let mut span = A;
+            span.set_end(1);
+        }
+    }
+
+    mod _0_1_2_generic_span {
+        use crate::{ioffset8, ioffset64, src_code::Span, test_assert, uoffset8, uoffset16, uoffset32, uoffset64};
+
+        const _: () = test_assert!(Span::<uoffset8>::new(0, 255), == Some(Span { start: 0, end: 255 }));
+        const _: () = test_assert!(Span::<uoffset64>::new(1, 0), == None);
+        const _: () = test_assert!(Span::<ioffset8>::new(-5, -1), == Some(Span { start: -5, end: -1 }));
+        const _: () = test_assert!(Span::<ioffset8>::new(-1, -5), == None);
+
+        const _: () = test_assert!(Span::<uoffset8> { start: 10, end: 250 }.len(), == 240);
+        const _: () = test_assert!(Span::<ioffset8> { start: -100, end: 100 }.checked_len(), == None);
+        const _: () = test_assert!(Span::<ioffset8> { start: -100, end: 20 }.checked_len(), == Some(120));
+        const _: () = test_assert!(Span::<uoffset8> { start: 250, end: 255 }.shift_by(1), == None);
+        const _: () = test_assert!(Span::<uoffset8> { start: 0, end: 5 }.grow(0, 251), == None);
+        const _: () = test_assert!(Span::<ioffset8> { start: -2, end: 2 }.shift_by(-126), == Some(Span { start: -128, end: -124 }));
+        const _: () = test_assert!(Span::<ioffset8> { start: -2, end: 2 }.contains(-1), == true);
+        const _: () = test_assert!(Span::<ioffset8> { start: -2, end: 2 }.slice("abcd"), == None);
+        const _: () = test_assert!(Span::<uoffset16> { start: 1, end: 2 }.slice("abcd"), == Some(_));
+
+        const _: () = test_assert!(Span::<uoffset32> { start: 1, end: 300 }.checked_cast::<uoffset16>(), == Some(Span { start: 1, end: 300 }));
+        const _: () = test_assert!(Span::<uoffset32> { start: 1, end: 300 }.checked_cast::<uoffset8>(), == None);
+        const _: () = test_assert!(Span::<ioffset8> { start: -1, end: 3 }.checked_cast::<uoffset32>(), == None);
+        const _: () = test_assert!(Span::<uoffset64> { start: 0, end: u64::MAX }.checked_cast::<ioffset64>(), == None);
+
+        #[test]
+        fn lossless_conversions() {
+            let small = Span::<uoffset8> { start: 1, end: 2 };
+            let span: Span = small.into();
+            assert_eq!(span, Span { start: 1, end: 2 });
+            let wide: Span<uoffset64> = span.into();
+            assert_eq!(wide, Span { start: 1, end: 2 });
+            let signed: Span<ioffset64> = Span::<ioffset8> { start: -1, end: 2 }.into();
+            assert_eq!(signed, Span { start: -1, end: 2 });
+        }
+    }
+
+    mod _0_1_2_functionality {
+        use crate::src_code::{
+            byte_column_of, column_of, display_width, line_starts, lines, normalize_line_endings, Bom,
+            ColumnKind, DecodeError, FileId, FileSpan, InvalidUtf8, Line, LineColumn, LineEnd,
+            LineEndCounts, RemappedSpan, Span, Spanned, SrcFile, SrcMap, TextEdit, EditError, SrcInput,
+            SrcLoader, SrcBytes, LoadError, Cursor, Expansions, ExpnId, ExpnKind, ExpnSpan, Indentation,
+            IndentEvent, IndentError, IndentStyle, suspicious_chars, suspicious_kind, confusable_ascii,
+            SuspiciousChar, SuspiciousKind, CompactSpan, SpanInterner, LineIndexer,
+        };
+        use crate::digit;
+        use crate::{uoffset8, uoffset32};
+
+        /// Deterministic pseudo-random numbers below the given bound
+        fn xorshift(seed: u32) -> impl FnMut(u32) -> u32 {
+            let mut state = seed;
+            return move |bound: u32| -> u32 {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                return state % bound;
+            };
+        }
+
+        #[test]
+        fn line_starts_line_endings() {
+            assert_eq!(line_starts(""), [0]);
+            assert_eq!(line_starts("a"), [0]);
+            assert_eq!(line_starts("a\n"), [0, 2]);
+            assert_eq!(line_starts("a\nb\r\nc\rd"), [0, 2, 5, 7]);
+            assert_eq!(line_starts("\r\r\n\n\r"), [0, 1, 3, 4, 5]);
+        }
+
+        #[test]
+        fn src_file_line_column() {
+            let src = SrcFile::new("test".to_owned(), "ab\ncd\r\n\refg".to_owned()).unwrap();
+            assert_eq!(src.lines_count(), 4);
+            assert_eq!(src.line_index(0), Some(0));
+            assert_eq!(src.line_index(2), Some(0));
+            assert_eq!(src.line_index(3), Some(1));
+            assert_eq!(src.line_index(6), Some(1));
+            assert_eq!(src.line_index(7), Some(2));
+            assert_eq!(src.line_index(8), Some(3));
+            assert_eq!(src.line_index(11), Some(3));
+            assert_eq!(src.line_index(12), None);
+
+            assert_eq!(src.line_column(0), Some(LineColumn { line: 1, column: 1 }));
+            assert_eq!(src.line_column(4), Some(LineColumn { line: 2, column: 2 }));
+            assert_eq!(src.line_column(10), Some(LineColumn { line: 4, column: 3 }));
+            assert_eq!(src.line_column(11), Some(LineColumn { line: 4, column: 4 }));
+
+            assert_eq!(src.line_text(0), Some("ab"));
+            assert_eq!(src.line_text(1), Some("cd"));
+            assert_eq!(src.line_text(2), Some(""));
+            assert_eq!(src.line_text(3), Some("efg"));
+            assert_eq!(src.line_text(4), None);
+            assert_eq!(src.line_span(1), Some(Span { start: 3, end: 7 }));
+
+            assert_eq!(
+                src.span_line_columns(Span { start: 1, end: 10 }),
+                Some((LineColumn { line: 1, column: 2 }, LineColumn { line: 4, column: 3 })),
+            );
+        }
+
+        #[test]
+        fn columns() {
+            let line = "a\u{e9}\t\u{4e2d}\u{1f600}b";
+            let display = ColumnKind::Display { tab_width: 4 };
+
+            // byte offsets of each character: 0 1 3 4 7 11 12
+            assert_eq!(column_of(line, 3, ColumnKind::Byte), Some(3));
+            assert_eq!(column_of(line, 3, ColumnKind::Char), Some(2));
+            assert_eq!(column_of(line, 11, ColumnKind::Char), Some(5));
+            assert_eq!(column_of(line, 11, ColumnKind::Utf16), Some(6));
+            assert_eq!(column_of(line, 12, ColumnKind::Utf16), Some(7));
+            assert_eq!(column_of(line, 3, display), Some(2));
+            assert_eq!(column_of(line, 4, display), Some(4));
+            assert_eq!(column_of(line, 7, display), Some(6));
+            assert_eq!(column_of(line, 11, display), Some(8));
+            assert_eq!(column_of(line, 2, ColumnKind::Char), None);
+            assert_eq!(column_of(line, 13, ColumnKind::Char), None);
+
+            assert_eq!(byte_column_of(line, 2, ColumnKind::Char), Some(3));
+            assert_eq!(byte_column_of(line, 6, ColumnKind::Utf16), Some(11));
+            assert_eq!(byte_column_of(line, 5, ColumnKind::Utf16), None);
+            assert_eq!(byte_column_of(line, 4, display), Some(4));
+            assert_eq!(byte_column_of(line, 3, display), None);
+            assert_eq!(byte_column_of(line, 9, display), Some(12));
+            assert_eq!(byte_column_of(line, 9, ColumnKind::Display { tab_width: 8 }), None);
+            assert_eq!(byte_column_of(line, 13, ColumnKind::Display { tab_width: 8 }), Some(12));
+            assert_eq!(byte_column_of(line, 2, ColumnKind::Byte), None);
+            assert_eq!(byte_column_of(line, 12, ColumnKind::Byte), Some(12));
+
+            assert_eq!(display_width('\u{4e2d}'), 2);
+            assert_eq!(display_width('\u{301}'), 0);
+            assert_eq!(display_width('a'), 1);
+        }
+
+        #[test]
+        fn src_map() {
+            let mut map = SrcMap::new();
+            let main = map.add_code("main.btf".to_owned(), "let x = 1;\n".to_owned()).unwrap();
+            let lib = map.add_code("lib.btf".to_owned(), "fn f() {}\nfn g() {}".to_owned()).unwrap();
+            assert_ne!(main, lib);
+            assert_eq!(map.files().len(), 2);
+            assert_eq!(map.name(lib), Some("lib.btf"));
+            assert_eq!(map.name(FileId(2)), None);
+
+            let span = FileSpan::new(lib, Span { start: 13, end: 14 });
+            assert_eq!(map.text(span), Some("g"));
+            let location = map.locate(span).unwrap();
+            assert_eq!(location.name, "lib.btf");
+            assert_eq!(location.start, LineColumn { line: 2, column: 4 });
+            assert_eq!(location.end, LineColumn { line: 2, column: 5 });
+            assert_eq!(location.to_string(), "lib.btf:2:4");
+
+            assert_eq!(map.text(FileSpan::new(main, Span { start: 4, end: 5 })), Some("x"));
+            assert_eq!(map.locate(FileSpan::new(main, Span { start: 4, end: 12 })), None);
+        }
+
+        #[test]
+        fn lines_iterator() {
+            let code = "a\nbc\r\n\rd";
+            let collected: Vec<(Line, LineEnd)> = lines(code).collect();
+            assert_eq!(collected, [
+                (Line { start: 0, end: 1 }, LineEnd::LF),
+                (Line { start: 2, end: 4 }, LineEnd::CRLF),
+                (Line { start: 6, end: 6 }, LineEnd::CR),
+                (Line { start: 7, end: 8 }, LineEnd::None),
+            ]);
+
+            let trailing: Vec<(Line, LineEnd)> = lines("a\n").collect();
+            assert_eq!(trailing, [(Line { start: 0, end: 1 }, LineEnd::LF), (Line { start: 2, end: 2 }, LineEnd::None)]);
+            let empty: Vec<(Line, LineEnd)> = lines("").collect();
+            assert_eq!(empty, [(Line { start: 0, end: 0 }, LineEnd::None)]);
+
+            for other_code in ["", "a", "\r\n", "a\rb\r\n\n\r", "\r\r\n\n"] {
+                let starts: Vec<uoffset32> = lines(other_code).map(|(line, line_end)| return line.end + line_end.len()).collect();
+                let mut expected = line_starts(other_code);
+                let _ = expected.remove(0);
+                expected.push(other_code.len() as uoffset32);
+                assert_eq!(starts, expected);
+            }
+        }
+
+        #[test]
+        fn line_end_counts() {
+            let counts = LineEndCounts::of("a\nb\r\nc\n");
+            assert_eq!(counts, LineEndCounts { lf: 2, crlf: 1, cr: 0 });
+            assert!(counts.is_mixed());
+            assert_eq!(counts.dominant(), Some(LineEnd::LF));
+
+            let crlf_counts = LineEndCounts::of("a\r\nb\r\n");
+            assert!(!crlf_counts.is_mixed());
+            assert_eq!(crlf_counts.dominant(), Some(LineEnd::CRLF));
+            assert_eq!(LineEndCounts::of("abc").dominant(), None);
+        }
+
+        #[test]
+        fn line_ending_normalization() {
+            let code = "ab\r\ncd\ref\r\n\ng";
+            let (normalized, remap) = normalize_line_endings(code);
+            assert_eq!(normalized, "ab\ncd\nef\n\ng");
+            assert!(!remap.is_identity());
+
+            // "ef" in the normalized code
+            let normalized_span = Span { start: 6, end: 8 };
+            assert_eq!(normalized_span.slice(&normalized), Some("ef"));
+            let original_span = remap.original_span(normalized_span);
+            assert_eq!(original_span.slice(code), Some("ef"));
+            assert_eq!(remap.normalized_span(original_span), normalized_span);
+
+            // whole lines including their terminators
+            let original_line = remap.original_span(Span { start: 6, end: 9 });
+            assert_eq!(original_line.slice(code), Some("ef\r\n"));
+            assert_eq!(remap.original_offset(2), 2);
+            assert_eq!(remap.normalized_offset(3), 2);
+            assert_eq!(remap.original_offset(normalized.len() as uoffset32), code.len() as uoffset32);
+
+            for offset in 0..=normalized.len() as uoffset32 {
+                assert_eq!(remap.normalized_offset(remap.original_offset(offset)), offset);
+            }
+
+            let (lf_normalized, lf_remap) = normalize_line_endings("a\nb");
+            assert_eq!(lf_normalized, "a\nb");
+            assert!(lf_remap.is_identity());
+        }
+
+        #[test]
+        fn bom_and_invalid_utf8() {
+            assert_eq!(Bom::detect(b"\xEF\xBB\xBFa"), Some(Bom::Utf8));
+            assert_eq!(Bom::detect(b"\xFF\xFEa\x00"), Some(Bom::Utf16Le));
+            assert_eq!(Bom::detect(b"\xFE\xFF\x00a"), Some(Bom::Utf16Be));
+            assert_eq!(Bom::detect(b"\xFF\xFE\x00\x00"), Some(Bom::Utf32Le));
+            assert_eq!(Bom::detect(b"\xEF\xBB"), None);
+
+            let (src, invalid) = SrcFile::from_bytes("bom".to_owned(), b"\xEF\xBB\xBFlet x;\n").unwrap();
+            assert!(src.has_bom());
+            assert_eq!(src.code(), "let x;\n");
+            assert!(invalid.is_empty());
+
+            let (latin1_src, latin1_invalid) = SrcFile::from_bytes("latin1".to_owned(), b"caf\xE9 = \xF0\x9F\x98;\n\xFF").unwrap();
+            assert!(!latin1_src.has_bom());
+            assert_eq!(latin1_src.code(), "caf\u{fffd} = \u{fffd};\n\u{fffd}");
+            assert_eq!(latin1_invalid, [
+                InvalidUtf8 { bytes: Span { start: 3, end: 4 }, span: Span { start: 3, end: 6 } },
+                InvalidUtf8 { bytes: Span { start: 7, end: 10 }, span: Span { start: 9, end: 12 } },
+                InvalidUtf8 { bytes: Span { start: 12, end: 13 }, span: Span { start: 14, end: 17 } },
+            ]);
+            assert_eq!(latin1_invalid[1].span.slice(latin1_src.code()), Some("\u{fffd}"));
+            assert_eq!(latin1_src.line_column(latin1_invalid[2].span.start), Some(LineColumn { line: 2, column: 1 }));
+
+            assert_eq!(
+                SrcFile::from_bytes("utf16".to_owned(), b"\xFF\xFEa\x00").unwrap_err(),
+                DecodeError::UnsupportedBom(Bom::Utf16Le),
+            );
+        }
+
+        #[test]
+        fn spanned() {
+            let src = SrcFile::new("test".to_owned(), "let x\n  = 42;".to_owned()).unwrap();
+            let number = Spanned::new(42, Span { start: 10, end: 12 });
+            assert_eq!(number.span(), Span { start: 10, end: 12 });
+            assert_eq!(*number.value(), 42);
+            assert_eq!(number.to_string(), "42 @ 10..12");
+            assert_eq!(number.display(&src).to_string(), "42 @ 2:5");
+
+            let text = number.map(|value| return value.to_string());
+            assert_eq!(text.as_ref().map(String::len), Spanned::new(2, Span { start: 10, end: 12 }));
+            assert_eq!(text.into_value(), "42");
+
+            let moved = Spanned::new(42, Span { start: 0, end: 2 });
+            assert_ne!(number, moved);
+            assert!(number.eq_ignore_span(&moved));
+            assert!(!number.eq_ignore_span(&Spanned::new(41, number.span)));
+
+            let mut counter = Spanned::new(0, Span { start: 1, end: 2 });
+            *counter.as_mut().value += 1;
+            assert_eq!(counter, Spanned::new(1, Span { start: 1, end: 2 }));
+
+            let outside = Spanned::new('x', Span { start: 100, end: 101 });
+            assert_eq!(outside.display(&src).to_string(), "x @ 100..101");
+
+            let narrow: Spanned<char, uoffset8> = Spanned::new('x', Span { start: 3, end: 4 });
+            assert_eq!(narrow.to_string(), "x @ 3..4");
+            assert!(narrow.eq_ignore_span(&outside));
+        }
+
+        #[test]
+        fn incremental_edits() {
+            let mut src = SrcFile::new("test".to_owned(), "ab\ncd\nef".to_owned()).unwrap();
+            src.apply_edit(&TextEdit::new(Span { start: 1, end: 4 }, "X\r\nY\rZ".to_owned())).unwrap();
+            assert_eq!(src.code(), "aX\r\nY\rZd\nef");
+            assert_eq!(src.line_starts(), line_starts(src.code()));
+
+            // splitting and merging `CRLF`s
+            src.apply_edit(&TextEdit::insert(3, "!".to_owned())).unwrap();
+            assert_eq!(src.code(), "aX\r!\nY\rZd\nef");
+            assert_eq!(src.line_starts(), line_starts(src.code()));
+            src.apply_edit(&TextEdit::delete(Span { start: 3, end: 4 })).unwrap();
+            assert_eq!(src.line_starts(), line_starts(src.code()));
+            src.apply_edit(&TextEdit::insert(7, "\n".to_owned())).unwrap();
+            assert_eq!(src.code(), "aX\r\nY\rZ\nd\nef");
+            assert_eq!(src.line_starts(), line_starts(src.code()));
+
+            assert_eq!(src.apply_edit(&TextEdit::delete(Span { start: 0, end: 100 })), Err(EditError::OutOfBounds));
+            let mut unicode = SrcFile::new("test".to_owned(), "\u{e9}".to_owned()).unwrap();
+            assert_eq!(unicode.apply_edit(&TextEdit::insert(1, String::new())), Err(EditError::NotCharBoundary));
+        }
+
+        #[test]
+        fn incremental_edits_match_full_recomputation() {
+            let mut random = xorshift(0x2545_F491);
+            let alphabet = ["a", "\n", "\r", "\r\n"];
+
+            for _ in 0..200 {
+                let mut code = String::new();
+                for _ in 0..random(20) {
+                    code.push_str(alphabet[random(4) as usize]);
+                }
+                let mut src = SrcFile::new("random".to_owned(), code).unwrap();
+
+                for _ in 0..20 {
+                    let len = src.len();
+                    let start = random(len + 1);
+                    let end = start + random(len - start + 1);
+                    let mut replacement = String::new();
+                    for _ in 0..random(4) {
+                        replacement.push_str(alphabet[random(4) as usize]);
+                    }
+                    src.apply_edit(&TextEdit::new(Span { start, end }, replacement)).unwrap();
+                    assert_eq!(src.line_starts(), line_starts(src.code()), "{:?}", src.code());
+                }
+            }
+        }
+
+        #[test]
+        fn remap_spans() {
+            // "let value = 1;" -> "let v = 1;"
+            let edit = TextEdit::new(Span { start: 4, end: 9 }, "v".to_owned());
+            assert_eq!(edit.delta(), -4);
+            assert_eq!(edit.remap(Span { start: 0, end: 3 }), RemappedSpan::Unchanged(Span { start: 0, end: 3 }));
+            assert_eq!(edit.remap(Span { start: 0, end: 4 }), RemappedSpan::Unchanged(Span { start: 0, end: 4 }));
+            assert_eq!(edit.remap(Span { start: 12, end: 13 }), RemappedSpan::Shifted(Span { start: 8, end: 9 }));
+            assert_eq!(edit.remap(Span { start: 9, end: 10 }), RemappedSpan::Shifted(Span { start: 5, end: 6 }));
+            assert_eq!(edit.remap(Span { start: 0, end: 14 }), RemappedSpan::Resized(Span { start: 0, end: 10 }));
+            assert_eq!(edit.remap(Span { start: 4, end: 9 }), RemappedSpan::Resized(Span { start: 4, end: 5 }));
+            assert_eq!(edit.remap(Span { start: 5, end: 7 }), RemappedSpan::Invalidated);
+            assert_eq!(edit.remap(Span { start: 2, end: 6 }), RemappedSpan::Clamped(Span { start: 2, end: 4 }));
+            assert_eq!(edit.remap(Span { start: 6, end: 11 }), RemappedSpan::Clamped(Span { start: 5, end: 7 }));
+            assert_eq!(RemappedSpan::Invalidated.span(), None);
+
+            let insertion = TextEdit::insert(4, "mut ".to_owned());
+            assert_eq!(insertion.remap(Span { start: 4, end: 9 }), RemappedSpan::Shifted(Span { start: 8, end: 13 }));
+            assert_eq!(insertion.remap(Span { start: 0, end: 4 }), RemappedSpan::Unchanged(Span { start: 0, end: 4 }));
+            assert_eq!(insertion.remap(Span { start: 0, end: 9 }), RemappedSpan::Resized(Span { start: 0, end: 13 }));
+        }
+
+        #[test]
+        fn load_sources() {
+            let loader = SrcLoader::new().with_max_len(8);
+            let (buffer, invalid_utf8) = loader.load(SrcInput::Buffer { name: "buffer", bytes: b"\xEF\xBB\xBFab\ncd" }).unwrap();
+            assert_eq!(buffer.name(), "buffer");
+            assert_eq!(buffer.code(), "ab\ncd");
+            assert!(invalid_utf8.is_empty(), "no invalid utf8 expected");
+
+            let too_large = loader.load(SrcInput::Buffer { name: "buffer", bytes: b"abcdefghi" });
+            assert!(matches!(too_large, Err(LoadError::TooLarge { len: 9, max_len: 8 })), "buffer should be too large");
+
+            let path = std::env::temp_dir().join(format!("back_to_front_load_{}.txt", std::process::id()));
+            std::fs::write(&path, "fn main() {}\n").unwrap();
+            let (file, _) = SrcLoader::new().load(SrcInput::Path(&path)).unwrap();
+            assert_eq!(file.code(), "fn main() {}\n");
+            assert_eq!(file.name(), path.display().to_string());
+
+            // SAFETY: the file is not modified while mapped
+            let mmap_loader = unsafe { SrcLoader::new().with_mmap(1) };
+            let mapped = mmap_loader.read(SrcInput::Path(&path)).unwrap();
+            #[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+            assert!(matches!(mapped, SrcBytes::Mapped(_)), "file should be memory mapped");
+            assert_eq!(mapped.as_bytes(), b"fn main() {}\n");
+            drop(mapped);
+
+            let (mut mapped_file, _) = mmap_loader.load(SrcInput::Path(&path)).unwrap();
+            #[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+            assert!(mapped_file.is_mapped(), "code should be kept in the mapping");
+            assert_eq!(mapped_file, file);
+            mapped_file.apply_edit(&TextEdit::insert(0, "pub ".to_owned())).unwrap();
+            assert!(!mapped_file.is_mapped(), "edited code should be copied out of the mapping");
+            assert_eq!(mapped_file.code(), "pub fn main() {}\n");
+            drop(mapped_file);
+
+            let file_too_large = SrcLoader::new().with_max_len(4).load(SrcInput::Path(&path));
+            assert!(matches!(file_too_large, Err(LoadError::TooLarge { len: 13, max_len: 4 })), "file should be too large");
+            std::fs::remove_file(&path).unwrap();
+
+            let missing = SrcLoader::new().load(SrcInput::Path(&path));
+            assert!(matches!(missing, Err(LoadError::Io(_))), "file should not exist");
+
+            let utf16 = SrcLoader::new().load(SrcInput::Buffer { name: "utf16", bytes: b"\xFF\xFEa\x00" });
+            assert!(matches!(utf16, Err(LoadError::Decode(DecodeError::UnsupportedBom(Bom::Utf16Le)))), "utf16 is not supported");
+        }
+
+        #[test]
+        fn cursor() {
+            let src = SrcFile::new("test".to_owned(), "0x1F_u8 + \u{e9}t".to_owned()).unwrap();
+            let mut cursor = Cursor::of(&src);
+            assert_eq!(cursor.peek(), Some(b'0'));
+            assert_eq!(cursor.peek_nth(1), Some(b'x'));
+            assert_eq!(cursor.peek_nth(100), None);
+
+            let literal = cursor.mark();
+            assert_eq!(cursor.bump(), Some(b'0'));
+            assert!(cursor.eat(b'x'), "expected hexadecimal prefix");
+            assert!(!cursor.eat(b'x'), "prefix already consumed");
+            let digits = cursor.eat_while(digit::is_hexadecimal);
+            assert_eq!(digits, Span { start: 2, end: 4 });
+            assert_eq!(cursor.eat_if(|byte| return byte == b'_'), Some(b'_'));
+            let _ = cursor.eat_while(|byte| return byte.is_ascii_alphanumeric());
+            assert_eq!(cursor.span_since(literal), Span { start: 0, end: 7 });
+            assert_eq!(cursor.span_since(literal).slice(src.code()), Some("0x1F_u8"));
+
+            let before_operator = cursor.mark();
+            assert_eq!(cursor.eat_while(|byte| return byte == b' ' || byte == b'+'), Span { start: 7, end: 10 });
+            cursor.rewind(before_operator);
+            assert_eq!(cursor.offset(), 7);
+            assert_eq!(cursor.eat_if(digit::is_decimal_digit), None);
+            let _ = cursor.eat_while(|byte| return byte != b'\xC3');
+
+            assert_eq!(cursor.peek_char(), Some('\u{e9}'));
+            assert_eq!(cursor.bump_char(), Some('\u{e9}'));
+            assert_eq!(cursor.remaining(), b"t");
+            assert_eq!(cursor.bump(), Some(b't'));
+            assert!(cursor.is_at_end(), "cursor should be at the end");
+            assert_eq!(cursor.bump(), None);
+            assert_eq!(cursor.bump_char(), None);
+        }
+
+        #[test]
+        fn cursor_rewind_past_end() {
+            let mut long = Cursor::new("0x1F_u8").unwrap();
+            let mut short = Cursor::new("0x").unwrap();
+            let _ = long.eat_while(|byte| return byte != b'\0');
+            short.rewind(long.mark());
+            assert_eq!(short.offset(), 2);
+            assert!(short.is_at_end(), "marks past the end should rewind to the end");
+            assert_eq!(short.remaining(), b"");
+        }
+
+        #[test]
+        fn expansions() {
+            let mut src_map = SrcMap::new();
+            let main = src_map.add_code("main.btf".to_owned(), "outer!(inner!())".to_owned()).unwrap();
+            let invocation = FileSpan::new(main, Span { start: 0, end: 16 });
+
+            let mut expansions = Expansions::new();
+            let outer = expansions.add(ExpnKind::Macro { name: "outer".to_owned() }, ExpnSpan::root(invocation));
+            let inner_call = ExpnSpan::new(FileSpan::new(main, Span { start: 7, end: 15 }), outer);
+            let inner = expansions.add(ExpnKind::Macro { name: "inner".to_owned() }, inner_call);
+            assert!(ExpnId::ROOT.is_root(), "root should be root");
+            assert!(!inner.is_root(), "expansions are not the root");
+            assert_eq!(expansions.data(ExpnId::ROOT), None);
+            assert_eq!(expansions.data(inner).unwrap().call_site, inner_call);
+
+            let generated = ExpnSpan::new(FileSpan::new(main, Span { start: 7, end: 15 }), inner);
+            let backtrace: Vec<String> = expansions.backtrace(generated).map(|data| return data.kind.to_string()).collect();
+            assert_eq!(backtrace, ["macro `inner`", "macro `outer`"]);
+            assert_eq!(expansions.user_span(generated), invocation);
+
+            let written = ExpnSpan::root(FileSpan::new(main, Span { start: 1, end: 2 }));
+            assert_eq!(expansions.backtrace(written).count(), 0);
+            assert_eq!(expansions.user_span(written), written.span);
+        }
+
+        #[should_panic]
+        #[test]
+        fn expansions_unknown_call_site() {
+            let mut expansions = Expansions::new();
+            let _ = expansions.add(ExpnKind::Desugaring { name: "`for` loop".to_owned() }, ExpnSpan::new(FileSpan::default(), ExpnId(1)));
+        }
+
+        #[test]
+        fn indentation() {
+            let blocks = Indentation::of("a:\n    b:\n\n  \n        c\n    d\ne\n");
+            assert_eq!(blocks.events, [
+                IndentEvent::Indent(Span { start: 3, end: 7 }),
+                IndentEvent::Indent(Span { start: 18, end: 22 }),
+                IndentEvent::Dedent(Span { start: 28, end: 28 }),
+                IndentEvent::Dedent(Span { start: 30, end: 30 }),
+            ]);
+            assert_eq!(blocks.errors, []);
+            assert_eq!(blocks.style, Some(IndentStyle::Spaces));
+
+            let unclosed = Indentation::of("a\r\n\tb\r\n\t\tc");
+            assert_eq!(unclosed.events, [
+                IndentEvent::Indent(Span { start: 3, end: 4 }),
+                IndentEvent::Indent(Span { start: 8, end: 9 }),
+                IndentEvent::Dedent(Span { start: 10, end: 10 }),
+                IndentEvent::Dedent(Span { start: 10, end: 10 }),
+            ]);
+            assert_eq!(unclosed.style, Some(IndentStyle::Tabs));
+
+            let unknown_dedent = Indentation::of("a\n    b\n  c\n");
+            assert_eq!(unknown_dedent.events, [
+                IndentEvent::Indent(Span { start: 2, end: 6 }),
+                IndentEvent::Dedent(Span { start: 10, end: 10 }),
+            ]);
+            assert_eq!(unknown_dedent.errors, [IndentError::UnknownDedent { span: Span { start: 8, end: 10 } }]);
+
+            let mixed = Indentation::of("a\n\tb\n  \tc\n");
+            assert_eq!(mixed.errors, [IndentError::MixedTabsAndSpaces { span: Span { start: 5, end: 8 }, expected: IndentStyle::Tabs }]);
+            assert_eq!(mixed.errors[0].to_string(), "inconsistent use of tabs and spaces, expected only tabs");
+
+            let flat = Indentation::of("a\nb\n");
+            assert_eq!(flat, Indentation { events: Vec::new(), errors: Vec::new(), style: None });
+        }
+
+        #[test]
+        fn suspicious_characters() {
+            // bidi overrides hiding a comment inside of a string literal
+            let code = "if l != \"user\u{202E} \u{2066}// admin\u{2069}\" {}\nlet \u{0430} = \u{FF11}\u{200B};\n";
+            let found = suspicious_chars(code);
+            assert_eq!(found, [
+                SuspiciousChar { span: Span { start: 13, end: 16 }, character: '\u{202E}', kind: SuspiciousKind::BidiControl },
+                SuspiciousChar { span: Span { start: 17, end: 20 }, character: '\u{2066}', kind: SuspiciousKind::BidiControl },
+                SuspiciousChar { span: Span { start: 28, end: 31 }, character: '\u{2069}', kind: SuspiciousKind::BidiControl },
+                SuspiciousChar { span: Span { start: 40, end: 42 }, character: '\u{0430}', kind: SuspiciousKind::Confusable(b'a') },
+                SuspiciousChar { span: Span { start: 45, end: 48 }, character: '\u{FF11}', kind: SuspiciousKind::Confusable(b'1') },
+                SuspiciousChar { span: Span { start: 48, end: 51 }, character: '\u{200B}', kind: SuspiciousKind::ZeroWidth },
+            ]);
+            for suspicious in &found {
+                assert_eq!(suspicious.span.slice(code).unwrap().chars().next(), Some(suspicious.character));
+            }
+
+            assert_eq!(found[0].to_string(), "unicode codepoint U+202E changes the visible direction of text");
+            assert_eq!(found[3].to_string(), "unicode codepoint U+0430 looks like 'a'");
+            assert_eq!(found[5].to_string(), "invisible unicode codepoint U+200B");
+            assert_eq!(confusable_ascii('\u{201C}'), Some(b'"'));
+            assert_eq!(confusable_ascii('\u{FF5E}'), Some(b'~'));
+            assert_eq!(confusable_ascii('\u{e9}'), None);
+            assert_eq!(suspicious_kind('a'), None);
+            assert!(suspicious_chars("plain ascii\tcode\r\n").is_empty(), "ascii is never suspicious");
+        }
+
+        #[test]
+        fn compact_spans() {
+            const _: () = assert!(size_of::<CompactSpan>() == size_of::<u32>(), "should be 32 bits");
+
+            let small = Span { start: 42, end: 50 };
+            let compact = CompactSpan::inline(small).unwrap();
+            assert!(!compact.is_interned(), "small spans are inline");
+            assert_eq!(compact.inline_span(), Some(small));
+            assert_eq!(CompactSpan::try_from(small), Ok(compact));
+
+            let largest = Span { start: CompactSpan::MAX_INLINE_START, end: CompactSpan::MAX_INLINE_START + CompactSpan::MAX_INLINE_LEN };
+            assert_eq!(CompactSpan::inline(largest).unwrap().inline_span(), Some(largest));
+            let far = Span { start: CompactSpan::MAX_INLINE_START + 1, end: CompactSpan::MAX_INLINE_START + 1 };
+            let long = Span { start: 0, end: CompactSpan::MAX_INLINE_LEN + 1 };
+            assert_eq!(CompactSpan::inline(far), None);
+            assert_eq!(CompactSpan::try_from(long), Err(long));
+
+            let main = FileId(0);
+            let lib = FileId(1);
+            let mut interner = SpanInterner::new(main);
+            assert_eq!(interner.intern(FileSpan::new(main, small)), compact);
+            let far_compact = interner.intern(FileSpan::new(main, far));
+            let long_compact = interner.intern(FileSpan::new(main, long));
+            assert!(far_compact.is_interned(), "far spans are interned");
+            assert_eq!(far_compact.inline_span(), None);
+            assert_ne!(far_compact, long_compact);
+            assert_eq!(interner.intern(FileSpan::new(main, far)), far_compact);
+            assert_eq!(interner.len(), 2);
+
+            let other_file_compact = interner.intern(FileSpan::new(lib, small));
+            assert!(other_file_compact.is_interned(), "spans in other files are interned");
+            assert_ne!(other_file_compact, compact);
+            assert_eq!(interner.len(), 3);
+
+            assert_eq!(interner.resolve(compact), Some(FileSpan::new(main, small)));
+            assert_eq!(interner.resolve(far_compact), Some(FileSpan::new(main, far)));
+            assert_eq!(interner.resolve(long_compact), Some(FileSpan::new(main, long)));
+            assert_eq!(interner.resolve(other_file_compact), Some(FileSpan::new(lib, small)));
+            assert_eq!(SpanInterner::new(lib).resolve(compact), Some(FileSpan::new(lib, small)));
+            assert_eq!(SpanInterner::new(main).resolve(long_compact), None);
+        }
+
+        #[test]
+        fn line_indexers_match_scalar() {
+            let mut indexers = vec![LineIndexer::Swar];
+            #[cfg(target_arch = "x86_64")]
+            indexers.extend([LineIndexer::Sse2, LineIndexer::Avx2]);
+            indexers.retain(|indexer| return indexer.is_supported());
+            assert!(LineIndexer::detect().is_supported(), "detected indexer should be supported");
+
+            let mut random = xorshift(0x9E37_79B9);
+            // `\u{8a}` and `\u{38d}` end with `\n | 0x80` and `\r | 0x80` continuation bytes
+            let alphabet = ["\n", "\r", "a", "\u{80}", "\u{7f}", "\u{8a}", "\u{38d}", "\0"];
+
+            for _ in 0..1000 {
+                let len = random(200) as usize;
+                let mut code = String::with_capacity(len);
+                for _ in 0..len {
+                    code.push_str(alphabet[random(alphabet.len() as u32) as usize]);
+                }
+
+                // shifting the start changes which chunk each byte falls in
+                for skip in 0..code.len().min(4) {
+                    let Some(shifted) = code.get(skip..) else {
+                        continue;
+                    };
+                    let expected = line_starts(shifted);
+                    assert_eq!(LineIndexer::Scalar.line_starts(shifted), expected);
+                    for indexer in &indexers {
+                        assert_eq!(indexer.line_starts(shifted), expected, "{indexer:?} {shifted:?}");
+                    }
+                }
+            }
+
+            let crlf_across_chunks = format!("{}\r\n{}\r{}\n", "a".repeat(31), "b".repeat(30), "c".repeat(63));
+            for indexer in &indexers {
+                assert_eq!(indexer.line_starts(&crlf_across_chunks), line_starts(&crlf_across_chunks), "{indexer:?}");
+            }
+        }
+
+        #[test]
+        fn src_file_columns() {
+            let src = SrcFile::new("test".to_owned(), "x\n\t\u{4e2d}y\n".to_owned()).unwrap();
+            let display = ColumnKind::Display { tab_width: 8 };
+            assert_eq!(src.line_column_of(6, display), Some(LineColumn { line: 2, column: 11 }));
+            assert_eq!(src.line_column_of(6, ColumnKind::Char), Some(LineColumn { line: 2, column: 3 }));
+            assert_eq!(src.offset_of(LineColumn { line: 2, column: 11 }, display), Some(6));
+            assert_eq!(src.offset_of(LineColumn { line: 2, column: 3 }, ColumnKind::Utf16), Some(6));
+            assert_eq!(src.offset_of(LineColumn { line: 2, column: 5 }, ColumnKind::Utf16), None);
+            assert_eq!(src.offset_of(LineColumn { line: 0, column: 1 }, ColumnKind::Byte), None);
+        }
     }
 
     #[expect(deprecated)]